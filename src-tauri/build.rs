@@ -20,5 +20,6 @@ fn main() {
         println!("cargo:rustc-link-lib=static=ws2_32");
     }
     
+    #[cfg(feature = "desktop")]
     tauri_build::build()
 }
@@ -0,0 +1,354 @@
+//! Command-line front end for scripts and CI. It shares `DatabaseManager` with the desktop
+//! app but needs no window, so it runs on headless machines.
+//!
+//! Exit codes: 0 when the command succeeded and, for the `diff-*` commands, found no
+//! differences; 1 when differences were found; 2 on errors.
+
+use clap::{Args, Parser, Subcommand};
+use sqlcipher_tool::database::{CancelToken, DatabaseManager};
+use sqlcipher_tool::error::AppError;
+use sqlcipher_tool::models::*;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "sqlcipher-tool-cli", version, about = "Inspect and compare SQLite and SQLCipher databases")]
+struct Cli {
+    /// Print JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+    /// Open encrypted databases with the first known SQLCipher cipher profile that accepts the
+    /// key, starting with the SQLCipher 4 defaults, instead of the defaults alone
+    #[arg(long, global = true)]
+    detect_cipher: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the tables of a database with their columns
+    Tables {
+        path: String,
+        #[command(flatten)]
+        key: Key,
+    },
+    /// Print the rows of a table
+    Dump {
+        path: String,
+        table: String,
+        /// Print at most this many rows
+        #[arg(long)]
+        limit: Option<i64>,
+        #[command(flatten)]
+        key: Key,
+    },
+    /// Compare the schemas of two databases; the second may also be a snapshot (.json) saved
+    /// by the desktop app
+    DiffSchema {
+        a: String,
+        b: String,
+        #[command(flatten)]
+        keys: KeyPair,
+        /// Only report type changes that change the column's affinity
+        #[arg(long)]
+        ignore_cosmetic_types: bool,
+    },
+    /// Compare the rows of tables present in both databases
+    DiffData {
+        a: String,
+        b: String,
+        /// Table to compare; repeat for several. Defaults to every table both databases have
+        #[arg(long = "table")]
+        tables: Vec<String>,
+        /// Match rows on these columns instead of the primary key
+        #[arg(long, value_delimiter = ',')]
+        key_columns: Vec<String>,
+        /// Cap on the rows listed per kind of difference; the counts stay exact
+        #[arg(long)]
+        max_rows: Option<usize>,
+        #[command(flatten)]
+        keys: KeyPair,
+    },
+}
+
+/// Key of a single database. Without any of these the database must be plaintext.
+#[derive(Args)]
+struct Key {
+    /// Passphrase
+    #[arg(long, conflicts_with_all = ["raw_key", "key_file"])]
+    key: Option<String>,
+    /// Raw key as 64 hex characters, or 96 with the database salt appended
+    #[arg(long, conflicts_with = "key_file")]
+    raw_key: Option<String>,
+    /// File holding the passphrase
+    #[arg(long)]
+    key_file: Option<String>,
+}
+
+#[derive(Args)]
+struct KeyPair {
+    /// Passphrase of the first database
+    #[arg(long, conflicts_with_all = ["raw_key_a", "key_file_a"])]
+    key_a: Option<String>,
+    /// Raw key of the first database (64 or 96 hex characters)
+    #[arg(long, conflicts_with = "key_file_a")]
+    raw_key_a: Option<String>,
+    /// File holding the passphrase of the first database
+    #[arg(long)]
+    key_file_a: Option<String>,
+    /// Passphrase of the second database
+    #[arg(long, conflicts_with_all = ["raw_key_b", "key_file_b"])]
+    key_b: Option<String>,
+    /// Raw key of the second database (64 or 96 hex characters)
+    #[arg(long, conflicts_with = "key_file_b")]
+    raw_key_b: Option<String>,
+    /// File holding the passphrase of the second database
+    #[arg(long)]
+    key_file_b: Option<String>,
+}
+
+impl Key {
+    fn material(&self) -> KeyMaterial {
+        key_material(&self.key, &self.raw_key, &self.key_file)
+    }
+}
+
+impl KeyPair {
+    fn material(&self) -> (KeyMaterial, KeyMaterial) {
+        (
+            key_material(&self.key_a, &self.raw_key_a, &self.key_file_a),
+            key_material(&self.key_b, &self.raw_key_b, &self.key_file_b),
+        )
+    }
+}
+
+fn key_material(passphrase: &Option<String>, raw_key: &Option<String>, key_file: &Option<String>) -> KeyMaterial {
+    if let Some(hex) = raw_key {
+        let hex = hex.trim();
+        // The manager validates the hex; only the split into key and salt happens here
+        if hex.len() == 96 && hex.is_ascii() {
+            let (key_hex, salt_hex) = hex.split_at(64);
+            return KeyMaterial::RawKeyWithSalt { key_hex: key_hex.to_string(), salt_hex: salt_hex.to_string() };
+        }
+        return KeyMaterial::RawKey { key_hex: hex.to_string() };
+    }
+    if let Some(path) = key_file {
+        return KeyMaterial::KeyFile { path: path.clone(), raw: false };
+    }
+    KeyMaterial::Passphrase { passphrase: passphrase.clone().unwrap_or_default() }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(differences) => ExitCode::from(u8::from(differences)),
+        Err(e) => {
+            eprintln!("error: {}", AppError::from(e));
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Run the command; `Ok(true)` means differences were found
+fn run(cli: &Cli) -> anyhow::Result<bool> {
+    let manager = DatabaseManager::new();
+    let cancel = CancelToken::new();
+    let connect = |path: &str, key: &KeyMaterial| -> anyhow::Result<ConnectionId> {
+        Ok(manager.connect_database(path, None, key, None, cli.detect_cipher, OpenMode::ReadOnly)?.id)
+    };
+
+    match &cli.command {
+        Command::Tables { path, key } => {
+            let id = connect(path, &key.material())?;
+            let tables = manager.get_tables(&id, &cancel)?;
+            if cli.json {
+                print_json(&tables)?;
+            } else {
+                for table in &tables {
+                    println!("{} ({} rows)", table.name, table.row_count);
+                    for column in &table.columns {
+                        let mut flags = Vec::new();
+                        if column.is_primary_key {
+                            flags.push("PRIMARY KEY");
+                        }
+                        if !column.is_nullable {
+                            flags.push("NOT NULL");
+                        }
+                        let line = format!("  {} {} {}", column.name, column.data_type, flags.join(" "));
+                        println!("{}", line.trim_end());
+                    }
+                }
+            }
+            Ok(false)
+        }
+        Command::Dump { path, table, limit, key } => {
+            let id = connect(path, &key.material())?;
+            let data = manager.get_table_data(&id, table, *limit, &cancel)?;
+            if cli.json {
+                print_json(&data)?;
+            } else {
+                println!("{}", data.columns.join("\t"));
+                for row in &data.rows {
+                    let cells: Vec<String> = row.iter().map(cell_text).collect();
+                    println!("{}", cells.join("\t"));
+                }
+                if (data.rows.len() as i64) < data.total_count {
+                    eprintln!("({} of {} rows)", data.rows.len(), data.total_count);
+                }
+            }
+            Ok(false)
+        }
+        Command::DiffSchema { a, b, keys, ignore_cosmetic_types } => {
+            let (key_a, key_b) = keys.material();
+            let options = SchemaCompareOptions {
+                type_comparison: if *ignore_cosmetic_types { TypeComparison::Affinity } else { TypeComparison::Declared },
+            };
+            let id_a = connect(a, &key_a)?;
+            let comparison = if b.to_ascii_lowercase().ends_with(".json") {
+                manager.compare_schema_with_snapshot(&id_a, b, &options, &cancel)?
+            } else {
+                let id_b = connect(b, &key_b)?;
+                manager.compare_schemas(&id_a, &id_b, &options, &cancel)?
+            };
+            if cli.json {
+                print_json(&comparison)?;
+            } else {
+                print_schema_comparison(&comparison);
+            }
+            Ok(comparison.has_differences())
+        }
+        Command::DiffData { a, b, tables, key_columns, max_rows, keys } => {
+            let (key_a, key_b) = keys.material();
+            let (id_a, id_b) = (connect(a, &key_a)?, connect(b, &key_b)?);
+            let tables = if tables.is_empty() {
+                let names_b: Vec<String> = manager.get_tables(&id_b, &cancel)?.into_iter().map(|t| t.name).collect();
+                manager.get_tables(&id_a, &cancel)?.into_iter()
+                    .map(|t| t.name)
+                    .filter(|name| names_b.contains(name))
+                    .collect()
+            } else {
+                tables.clone()
+            };
+            let options = DataCompareOptions {
+                max_rows: *max_rows,
+                chunk_size: None,
+                key: (!key_columns.is_empty()).then(|| RowKey::Columns { columns: key_columns.clone() }),
+            };
+
+            let mut comparisons = Vec::new();
+            for table in &tables {
+                comparisons.push(manager.compare_table_data(&id_a, &id_b, table, &options, &cancel)?);
+            }
+            if cli.json {
+                print_json(&comparisons)?;
+            } else {
+                for comparison in &comparisons {
+                    print_data_comparison(comparison);
+                }
+            }
+            Ok(comparisons.iter().any(|c| c.summary.has_differences()))
+        }
+    }
+}
+
+fn print_json<T: serde::Serialize>(value: &T) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn cell_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => "NULL".to_string(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn print_schema_comparison(comparison: &SchemaComparison) {
+    println!("--- {}", comparison.database1);
+    println!("+++ {}", comparison.database2);
+    if !comparison.has_differences() {
+        println!("Schemas are identical ({} tables)", comparison.identical_tables.len());
+        return;
+    }
+
+    for table in &comparison.added_tables {
+        println!("+ table {}", table);
+    }
+    for table in &comparison.removed_tables {
+        println!("- table {}", table);
+    }
+    for diff in &comparison.modified_tables {
+        println!("~ table {}", diff.table_name);
+        for column in &diff.added_columns {
+            println!("    + column {} {}", column.name, column.data_type);
+        }
+        for column in &diff.removed_columns {
+            println!("    - column {}", column);
+        }
+        for column in &diff.modified_columns {
+            println!("    ~ column {}: {}", column.column_name, column.changes.join(", "));
+        }
+        for rename in &diff.renamed_columns {
+            println!("    ? column {} may have been renamed to {}", rename.old_name, rename.new_name);
+        }
+        for moved in &diff.moved_columns {
+            println!("    ~ column {} moved: position {} -> {}", moved.column_name, moved.old_position + 1, moved.new_position + 1);
+        }
+        for key in &diff.added_foreign_keys {
+            println!("    + foreign key ({}) -> {}", key.columns.join(", "), key.referenced_table);
+        }
+        for key in &diff.removed_foreign_keys {
+            println!("    - foreign key ({}) -> {}", key.columns.join(", "), key.referenced_table);
+        }
+        for key in &diff.modified_foreign_keys {
+            println!("    ~ foreign key ({}): {}", key.columns.join(", "), key.changes.join(", "));
+        }
+        for change in &diff.changes {
+            println!("    ~ {}", change);
+        }
+    }
+
+    let objects = [
+        ("index", &comparison.added_indexes, &comparison.removed_indexes, &comparison.modified_indexes),
+        ("trigger", &comparison.added_triggers, &comparison.removed_triggers, &comparison.modified_triggers),
+        ("view", &comparison.added_views, &comparison.removed_views, &comparison.modified_views),
+    ];
+    for (kind, added, removed, modified) in objects {
+        for name in added {
+            println!("+ {} {}", kind, name);
+        }
+        for name in removed {
+            println!("- {} {}", kind, name);
+        }
+        for object in modified {
+            println!("~ {} {}: {}", kind, object.name, object.changes.join(", "));
+        }
+    }
+    for change in &comparison.pragma_changes {
+        println!("~ pragma {}", change);
+    }
+}
+
+fn print_data_comparison(comparison: &DataComparison) {
+    let summary = &comparison.summary;
+    println!(
+        "{}: {} added, {} removed, {} changed, {} identical",
+        comparison.table_name, summary.added, summary.removed, summary.changed, summary.identical
+    );
+    for row in &comparison.added_rows {
+        println!("    + {}", row);
+    }
+    for row in &comparison.removed_rows {
+        println!("    - {}", row);
+    }
+    for row in &comparison.changed_rows {
+        let changes: Vec<String> = row.changes.iter()
+            .map(|c| format!("{}: {} -> {}", c.column, c.old_value, c.new_value))
+            .collect();
+        println!("    ~ {} {}", row.key, changes.join(", "));
+    }
+    if summary.truncated {
+        println!("    (row lists truncated)");
+    }
+}
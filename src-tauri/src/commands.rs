@@ -1,48 +1,189 @@
-use crate::database::DatabaseManager;
-use crate::models::*;
-use std::sync::Mutex;
+use sqlcipher_tool::database::{CancelToken, DatabaseManager, ProgressSink};
+use sqlcipher_tool::error::AppError;
+use sqlcipher_tool::models::*;
+use std::sync::Arc;
 use std::time::Duration;
-use tauri::State;
-use tokio::time::timeout;
+use tauri::{AppHandle, Emitter, State};
+use tokio::time::{error::Elapsed, timeout};
 
-// Global database manager
-type DbManager = Mutex<DatabaseManager>;
+// Global database manager; it locks internally so commands never hold a global lock
+type DbManager = Arc<DatabaseManager>;
+
+/// Event carrying an `OperationProgress` payload
+const PROGRESS_EVENT: &str = "operation://progress";
+
+/// Forward an operation's progress updates to the frontend as `operation://progress` events
+fn progress_events(app: &AppHandle) -> ProgressSink {
+    let app = app.clone();
+    Arc::new(move |progress: OperationProgress| {
+        if let Err(e) = app.emit(PROGRESS_EVENT, progress) {
+            println!("Failed to emit progress event: {}", e);
+        }
+    })
+}
+
+/// Run blocking database work on the blocking thread pool instead of the async runtime.
+/// The operation is registered under `operation_id` (or a generated id) so `cancel_operation`
+/// can abort it; on timeout it is cancelled the same way and `Err(Elapsed)` is returned.
+/// Progress updates go to `progress` when one is given.
+async fn run_operation<T, F>(
+    manager: &State<'_, DbManager>,
+    operation_id: Option<String>,
+    connections: &[ConnectionId],
+    progress: Option<ProgressSink>,
+    time_limit: Option<Duration>,
+    work: F,
+) -> Result<anyhow::Result<T>, Elapsed>
+where
+    T: Send + 'static,
+    F: FnOnce(&DatabaseManager, &CancelToken) -> anyhow::Result<T> + Send + 'static,
+{
+    let manager = manager.inner().clone();
+    let operation_id = operation_id.unwrap_or_else(|| manager.next_operation_id());
+    let cancel = match manager.begin_operation(&operation_id, connections, progress) {
+        Ok(cancel) => cancel,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    let task = {
+        let manager = manager.clone();
+        tauri::async_runtime::spawn_blocking(move || work(&manager, &cancel))
+    };
+
+    let joined = match time_limit {
+        Some(limit) => match timeout(limit, task).await {
+            Ok(joined) => joined,
+            Err(elapsed) => {
+                // Abort the running statement; the blocking task then finishes on its own
+                manager.cancel_operation(&operation_id);
+                manager.end_operation(&operation_id);
+                return Err(elapsed);
+            }
+        },
+        None => task.await,
+    };
+    manager.end_operation(&operation_id);
+
+    Ok(joined.unwrap_or_else(|e| Err(anyhow::anyhow!("Database task failed: {}", e))))
+}
 
 #[tauri::command]
 pub async fn connect_database(
     path: String,
-    password: String,
+    alias: Option<String>,
+    password: Option<String>,
+    key: Option<KeyMaterial>,
+    cipher_settings: Option<CipherSettings>,
+    detect_cipher: Option<bool>,
+    open_mode: Option<OpenMode>,
     manager: State<'_, DbManager>,
-) -> Result<DatabaseInfo, String> {
-    let mut db_manager = manager.lock().unwrap();
+) -> Result<DatabaseInfo, AppError> {
+    // Explicit key material wins; a bare password is treated as a passphrase
+    let key = key.unwrap_or(KeyMaterial::Passphrase {
+        passphrase: password.unwrap_or_default(),
+    });
+    let connect_path = path.clone();
+
+    // Key derivation (and cipher detection) is slow, so this runs off the async runtime too
+    let result = run_operation(&manager, None, &[], None, None, move |db_manager, _| {
+        db_manager.connect_database(&connect_path, alias, &key, cipher_settings, detect_cipher.unwrap_or(false), open_mode.unwrap_or_default())
+    }).await.unwrap_or_else(|e| Err(e.into()));
     
-    match db_manager.connect_database(&path, &password) {
+    match result {
         Ok(db_info) => {
-            println!("Successfully connected to database: {}", path);
+            println!("Successfully connected to database: {} ({})", path, db_info.id);
             Ok(db_info)
         },
         Err(e) => {
             println!("Failed to connect to database {}: {}", path, e);
-            Err(format!("Connection failed: {}", e))
+            Err(AppError::from(e))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn disconnect_database(
+    connection_id: ConnectionId,
+    manager: State<'_, DbManager>,
+) -> Result<DatabaseInfo, AppError> {
+    match manager.disconnect_database(&connection_id) {
+        Ok(db_info) => {
+            println!("Disconnected database: {} ({})", db_info.path, connection_id);
+            Ok(db_info)
+        },
+        Err(e) => {
+            println!("Failed to disconnect database {}: {}", connection_id, e);
+            Err(AppError::from(e))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn disconnect_all(
+    manager: State<'_, DbManager>,
+) -> Result<Vec<DatabaseInfo>, AppError> {
+    match manager.disconnect_all() {
+        Ok(closed) => {
+            println!("Disconnected {} databases", closed.len());
+            Ok(closed)
+        },
+        Err(e) => {
+            println!("Failed to disconnect all databases: {}", e);
+            Err(AppError::from(e))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn list_connections(
+    manager: State<'_, DbManager>,
+) -> Result<Vec<DatabaseInfo>, AppError> {
+    Ok(manager.list_connections())
+}
+
+#[tauri::command]
+pub async fn reconnect_database(
+    connection_id: ConnectionId,
+    open_mode: Option<OpenMode>,
+    manager: State<'_, DbManager>,
+) -> Result<DatabaseInfo, AppError> {
+    let target = connection_id.clone();
+    let result = run_operation(&manager, None, &[], None, None, move |db_manager, _| {
+        db_manager.reconnect_database(&target, open_mode)
+    }).await.unwrap_or_else(|e| Err(e.into()));
+
+    match result {
+        Ok(db_info) => {
+            println!("Reconnected database: {} ({})", db_info.path, connection_id);
+            Ok(db_info)
+        },
+        Err(e) => {
+            println!("Failed to reconnect database {}: {}", connection_id, e);
+            Err(AppError::from(e))
         }
     }
 }
 
 #[tauri::command]
 pub async fn get_database_tables(
-    db_path: String,
+    connection_id: ConnectionId,
+    operation_id: Option<String>,
+    app: AppHandle,
     manager: State<'_, DbManager>,
-) -> Result<Vec<TableInfo>, String> {
-    let db_manager = manager.lock().unwrap();
+) -> Result<Vec<TableInfo>, AppError> {
+    let target = connection_id.clone();
+    let result = run_operation(&manager, operation_id, &[connection_id.clone()], Some(progress_events(&app)), None, move |db_manager, cancel| {
+        db_manager.get_tables(&target, cancel)
+    }).await.unwrap_or_else(|e| Err(e.into()));
     
-    match db_manager.get_tables(&db_path) {
+    match result {
         Ok(tables) => {
-            println!("Retrieved {} tables from {}", tables.len(), db_path);
+            println!("Retrieved {} tables from {}", tables.len(), connection_id);
             Ok(tables)
         },
         Err(e) => {
-            println!("Failed to get tables from {}: {}", db_path, e);
-            Err(format!("Failed to get tables: {}", e))
+            println!("Failed to get tables from {}: {}", connection_id, e);
+            Err(AppError::from(e))
         }
     }
 }
@@ -50,22 +191,23 @@ pub async fn get_database_tables(
 
 #[tauri::command]
 pub async fn get_table_data(
-    db_path: String,
+    connection_id: ConnectionId,
     table_name: String,
     limit: Option<i64>,
+    operation_id: Option<String>,
+    app: AppHandle,
     manager: State<'_, DbManager>,
-) -> Result<TableData, String> {
-    // Add timeout for large queries (60 seconds)
+) -> Result<TableData, AppError> {
+    // Add timeout for large queries (60 seconds); the query is interrupted when it fires
     let timeout_duration = Duration::from_secs(60);
-    
-    let result = timeout(timeout_duration, async {
-        let db_manager = manager.lock().unwrap();
-        
-        // Log the request with more details
-        println!("Fetching table '{}' from '{}' with limit: {:?}", 
-                table_name, db_path, limit);
-        
-        db_manager.get_table_data(&db_path, &table_name, limit)
+
+    // Log the request with more details
+    println!("Fetching table '{}' from '{}' with limit: {:?}", 
+            table_name, connection_id, limit);
+
+    let (target, table) = (connection_id.clone(), table_name.clone());
+    let result = run_operation(&manager, operation_id, &[connection_id.clone()], Some(progress_events(&app)), Some(timeout_duration), move |db_manager, cancel| {
+        db_manager.get_table_data(&target, &table, limit, cancel)
     }).await;
     
     match result {
@@ -73,20 +215,20 @@ pub async fn get_table_data(
             match data_result {
                 Ok(data) => {
                     println!("Successfully retrieved {} rows from table '{}' in '{}'", 
-                            data.rows.len(), table_name, db_path);
+                            data.rows.len(), table_name, connection_id);
                     Ok(data)
                 },
                 Err(e) => {
                     println!("Database error for table '{}': {}", table_name, e);
-                    Err(format!("Database error: {}", e))
+                    Err(AppError::from(e))
                 }
             }
         },
         Err(_) => {
             println!("Timeout occurred while fetching table '{}' (limit: {:?})", 
                     table_name, limit);
-            Err(format!("Query timeout - table '{}' took too long to fetch. Try using a smaller row limit.", 
-                       table_name))
+            Err(AppError::Timeout(format!("Table '{}' took too long to fetch. Try using a smaller row limit.", 
+                       table_name)))
         }
     }
 }
@@ -94,26 +236,212 @@ pub async fn get_table_data(
 
 #[tauri::command]
 pub async fn compare_database_schemas(
-    db1_path: String,
-    db2_path: String,
+    db1: ConnectionId,
+    db2: ConnectionId,
+    options: Option<SchemaCompareOptions>,
+    operation_id: Option<String>,
+    app: AppHandle,
     manager: State<'_, DbManager>,
-) -> Result<SchemaComparison, String> {
-    let db_manager = manager.lock().unwrap();
+) -> Result<SchemaComparison, AppError> {
+    let (left, right) = (db1.clone(), db2.clone());
+    let options = options.unwrap_or_default();
+    let result = run_operation(&manager, operation_id, &[db1.clone(), db2.clone()], Some(progress_events(&app)), None, move |db_manager, cancel| {
+        db_manager.compare_schemas(&left, &right, &options, cancel)
+    }).await.unwrap_or_else(|e| Err(e.into()));
     
-    match db_manager.compare_schemas(&db1_path, &db2_path) {
+    match result {
         Ok(comparison) => {
-            println!("Schema comparison completed between {} and {}", db1_path, db2_path);
+            println!("Schema comparison completed between {} and {}", db1, db2);
             Ok(comparison)
         },
         Err(e) => {
             println!("Failed to compare schemas: {}", e);
-            Err(format!("Schema comparison failed: {}", e))
+            Err(AppError::from(e))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn export_schema_snapshot(
+    connection_id: ConnectionId,
+    path: String,
+    operation_id: Option<String>,
+    app: AppHandle,
+    manager: State<'_, DbManager>,
+) -> Result<SchemaSnapshot, AppError> {
+    let (target, snapshot_path) = (connection_id.clone(), path.clone());
+    let result = run_operation(&manager, operation_id, &[connection_id.clone()], Some(progress_events(&app)), None, move |db_manager, cancel| {
+        db_manager.save_schema_snapshot(&target, &snapshot_path, cancel)
+    }).await.unwrap_or_else(|e| Err(e.into()));
+
+    match result {
+        Ok(snapshot) => {
+            println!("Saved schema snapshot of {} to {} ({} tables)", connection_id, path, snapshot.schema.tables.len());
+            Ok(snapshot)
+        },
+        Err(e) => {
+            println!("Failed to save schema snapshot of {}: {}", connection_id, e);
+            Err(AppError::from(e))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn compare_schema_with_snapshot(
+    connection_id: ConnectionId,
+    snapshot_path: String,
+    options: Option<SchemaCompareOptions>,
+    operation_id: Option<String>,
+    app: AppHandle,
+    manager: State<'_, DbManager>,
+) -> Result<SchemaComparison, AppError> {
+    let (target, path) = (connection_id.clone(), snapshot_path.clone());
+    let options = options.unwrap_or_default();
+    let result = run_operation(&manager, operation_id, &[connection_id.clone()], Some(progress_events(&app)), None, move |db_manager, cancel| {
+        db_manager.compare_schema_with_snapshot(&target, &path, &options, cancel)
+    }).await.unwrap_or_else(|e| Err(e.into()));
+
+    match result {
+        Ok(comparison) => {
+            println!("Schema comparison completed between {} and snapshot {}", connection_id, snapshot_path);
+            Ok(comparison)
+        },
+        Err(e) => {
+            println!("Failed to compare {} with snapshot {}: {}", connection_id, snapshot_path, e);
+            Err(AppError::from(e))
         }
     }
 }
 
 #[tauri::command]
-pub async fn test_connection() -> Result<String, String> {
+pub async fn generate_migration_script(
+    db1: ConnectionId,
+    db2: ConnectionId,
+    renames: Option<Vec<ColumnRename>>,
+    operation_id: Option<String>,
+    app: AppHandle,
+    manager: State<'_, DbManager>,
+) -> Result<MigrationScript, AppError> {
+    let (left, right) = (db1.clone(), db2.clone());
+    let renames = renames.unwrap_or_default();
+    let result = run_operation(&manager, operation_id, &[db1.clone(), db2.clone()], Some(progress_events(&app)), None, move |db_manager, cancel| {
+        db_manager.generate_migration_script(&left, &right, &renames, cancel)
+    }).await.unwrap_or_else(|e| Err(e.into()));
+
+    match result {
+        Ok(script) => {
+            println!("Generated migration script for {} from {} ({} tables)", script.target, script.source, script.tables.len());
+            Ok(script)
+        },
+        Err(e) => {
+            println!("Failed to generate migration script between {} and {}: {}", db1, db2, e);
+            Err(AppError::from(e))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn compare_table_data(
+    db1: ConnectionId,
+    db2: ConnectionId,
+    table_name: String,
+    options: Option<DataCompareOptions>,
+    operation_id: Option<String>,
+    app: AppHandle,
+    manager: State<'_, DbManager>,
+) -> Result<DataComparison, AppError> {
+    let (left, right, table) = (db1.clone(), db2.clone(), table_name.clone());
+    let options = options.unwrap_or_default();
+    let result = run_operation(&manager, operation_id, &[db1.clone(), db2.clone()], Some(progress_events(&app)), None, move |db_manager, cancel| {
+        db_manager.compare_table_data(&left, &right, &table, &options, cancel)
+    }).await.unwrap_or_else(|e| Err(e.into()));
+
+    match result {
+        Ok(comparison) => {
+            println!("Data comparison of '{}' completed between {} and {}: {} added, {} removed, {} changed",
+                    table_name, db1, db2, comparison.summary.added, comparison.summary.removed, comparison.summary.changed);
+            Ok(comparison)
+        },
+        Err(e) => {
+            println!("Failed to compare data of '{}': {}", table_name, e);
+            Err(AppError::from(e))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn generate_sync_script(
+    db1: ConnectionId,
+    db2: ConnectionId,
+    tables: Vec<String>,
+    options: Option<SyncOptions>,
+    operation_id: Option<String>,
+    app: AppHandle,
+    manager: State<'_, DbManager>,
+) -> Result<SyncScript, AppError> {
+    let (left, right) = (db1.clone(), db2.clone());
+    let options = options.unwrap_or_default();
+    let result = run_operation(&manager, operation_id, &[db1.clone(), db2.clone()], Some(progress_events(&app)), None, move |db_manager, cancel| {
+        db_manager.generate_sync_script(&left, &right, &tables, &options, cancel)
+    }).await.unwrap_or_else(|e| Err(e.into()));
+
+    match result {
+        Ok(script) => {
+            println!("Generated sync script from {} to {} for {} tables", script.source, script.target, script.tables.len());
+            Ok(script)
+        },
+        Err(e) => {
+            println!("Failed to generate sync script between {} and {}: {}", db1, db2, e);
+            Err(AppError::from(e))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn apply_sync(
+    db1: ConnectionId,
+    db2: ConnectionId,
+    tables: Vec<String>,
+    options: Option<SyncOptions>,
+    dry_run: Option<bool>,
+    operation_id: Option<String>,
+    app: AppHandle,
+    manager: State<'_, DbManager>,
+) -> Result<SyncResult, AppError> {
+    let (left, right) = (db1.clone(), db2.clone());
+    let options = options.unwrap_or_default();
+    let dry_run = dry_run.unwrap_or(false);
+    let result = run_operation(&manager, operation_id, &[db1.clone(), db2.clone()], Some(progress_events(&app)), None, move |db_manager, cancel| {
+        db_manager.apply_sync(&left, &right, &tables, &options, dry_run, cancel)
+    }).await.unwrap_or_else(|e| Err(e.into()));
+
+    match result {
+        Ok(sync) => {
+            println!(
+                "Applied sync from {} to {} (dry run: {}, committed: {}, conflicts: {})",
+                sync.source, sync.target, sync.dry_run, sync.committed, sync.conflicts.len()
+            );
+            Ok(sync)
+        },
+        Err(e) => {
+            println!("Failed to apply sync between {} and {}: {}", db1, db2, e);
+            Err(AppError::from(e))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn cancel_operation(
+    operation_id: String,
+    manager: State<'_, DbManager>,
+) -> Result<bool, AppError> {
+    let cancelled = manager.cancel_operation(&operation_id);
+    println!("Cancel requested for operation {} (running: {})", operation_id, cancelled);
+    Ok(cancelled)
+}
+
+#[tauri::command]
+pub async fn test_connection() -> Result<String, AppError> {
     Ok("Tauri backend is working!".to_string())
 }
 
@@ -359,71 +359,552 @@
 // }
 
 
-use rusqlite::{Connection, Result as RusqliteResult, types::Value};
+use rusqlite::{Connection, InterruptHandle, OpenFlags, OptionalExtension, Result as RusqliteResult, types::{Value, ValueRef}};
+use rusqlite::functions::{Aggregate, Context as FunctionContext, FunctionFlags};
+use crate::diff;
+use crate::error::AppError;
 use crate::models::*;
+use crate::sql::*;
 use std::collections::HashMap;
-use anyhow::{Context, Result};
+use anyhow::Context;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 
+/// Shared across Tauri commands without an outer lock: the connection map is only locked
+/// briefly, and each connection has its own lock so work on one database never blocks another
 pub struct DatabaseManager {
-    connections: HashMap<String, Connection>,
+    connections: Mutex<HashMap<ConnectionId, Arc<OpenDatabase>>>,
+    operations: Mutex<HashMap<String, RunningOperation>>,
+    next_connection_id: AtomicU64,
+    next_operation_id: AtomicU64,
 }
 
-#[derive(Debug)]
+/// A live connection plus what is needed to describe and reopen it
+struct OpenDatabase {
+    /// Order in which the connection was first opened; kept across reconnects
+    sequence: u64,
+    conn: Mutex<Connection>,
+    interrupt: InterruptHandle,
+    /// Cancellation flag of the operation holding `conn`, so cancelling an operation that is
+    /// still waiting for the connection leaves the one running on it alone
+    holder: Mutex<Option<Arc<AtomicBool>>>,
+    info: DatabaseInfo,
+    key: KeyMaterial,
+}
+
+impl OpenDatabase {
+    /// Lock the connection on behalf of the operation `cancel` belongs to
+    fn hold(&self, cancel: &CancelToken) -> HeldConnection<'_> {
+        let conn = self.conn.lock().unwrap();
+        *self.holder.lock().unwrap() = Some(cancel.cancelled.clone());
+        HeldConnection { db: self, conn }
+    }
+
+    /// Interrupt the running statement, but only if `cancel`'s operation holds the connection
+    fn interrupt_held_by(&self, cancel: &CancelToken) {
+        // The holder cannot release the connection while this lock is held
+        let holder = self.holder.lock().unwrap();
+        if holder.as_ref().is_some_and(|held| Arc::ptr_eq(held, &cancel.cancelled)) {
+            self.interrupt.interrupt();
+        }
+    }
+}
+
+/// A locked connection, marked with the operation holding it until dropped
+struct HeldConnection<'a> {
+    db: &'a OpenDatabase,
+    conn: MutexGuard<'a, Connection>,
+}
+
+impl std::ops::Deref for HeldConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+impl Drop for HeldConnection<'_> {
+    /// Runs before `conn` is unlocked, so the next holder never sees a stale mark
+    fn drop(&mut self) {
+        *self.db.holder.lock().unwrap() = None;
+    }
+}
+
+/// Receives progress updates for one operation; the Tauri layer forwards them as events
+pub type ProgressSink = Arc<dyn Fn(OperationProgress) + Send + Sync>;
+
+/// Handle passed to a running operation: the cancellation flag shared with
+/// `cancel_operation`, plus an optional sink for progress updates
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    progress: Option<(String, ProgressSink)>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Token that reports progress for `operation_id` to `sink`
+    pub fn with_progress(operation_id: &str, sink: ProgressSink) -> Self {
+        Self {
+            cancelled: Arc::default(),
+            progress: Some((operation_id.to_string(), sink)),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn report(&self, phase: ProgressPhase, connection_id: Option<&ConnectionId>, current_table: Option<&str>, done: u64, total: u64) {
+        if let Some((operation_id, sink)) = &self.progress {
+            sink(OperationProgress {
+                operation_id: operation_id.clone(),
+                phase,
+                connection_id: connection_id.cloned(),
+                current_table: current_table.map(str::to_string),
+                done,
+                total,
+            });
+        }
+    }
+
+    /// Fail fast between statements once the operation has been cancelled
+    fn check(&self) -> anyhow::Result<()> {
+        if self.is_cancelled() {
+            return Err(AppError::Cancelled.into());
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancelToken")
+            .field("cancelled", &self.is_cancelled())
+            .field("operation_id", &self.progress.as_ref().map(|(id, _)| id))
+            .finish()
+    }
+}
+
+/// An operation registered under an id so it can be cancelled from another command
+struct RunningOperation {
+    cancel: CancelToken,
+    databases: Vec<Arc<OpenDatabase>>,
+}
+
+/// Size of the SQLite database header
+const SQLITE_HEADER_SIZE: usize = 100;
+/// Magic string at the start of every plaintext SQLite header
+const SQLITE_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+/// Bytes SQLCipher leaves unencrypted in plaintext-header mode
+const PLAINTEXT_HEADER_SIZE: u32 = 32;
+/// Minimum Shannon entropy (bits per byte) of a 100-byte header to treat it as ciphertext
+const ENCRYPTED_ENTROPY_THRESHOLD: f64 = 5.5;
+/// Rows processed between two row-level progress updates
+const ROW_PROGRESS_INTERVAL: u64 = 1000;
+/// Row key used when a table has no primary key
+pub(crate) const ROWID: &str = "rowid";
+/// Key ranges with at most this many rows in database1 are merged instead of split further
+const LEAF_RANGE_ROWS: u64 = 512;
+/// SQL aggregate registered on every connection; see `RowDigest`
+const ROW_DIGEST_FUNCTION: &str = "sqlcipher_tool_row_digest";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DatabaseType {
-    SQLite,      // Regular unencrypted SQLite
-    SQLCipher,   // Encrypted SQLCipher
+    Plain,                      // Regular unencrypted SQLite
+    Encrypted,                  // SQLCipher, fully encrypted including the header
+    PlaintextHeaderEncrypted,   // SQLCipher with cipher_plaintext_header_size (e.g. iOS shared containers)
+    NotADatabase,               // Readable file that is neither SQLite nor SQLCipher
+    Empty,                      // Zero-length file
+    Missing,                    // Nothing at the given path
+}
+
+impl Default for DatabaseManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DatabaseManager {
     pub fn new() -> Self {
         Self {
-            connections: HashMap::new(),
+            connections: Mutex::new(HashMap::new()),
+            operations: Mutex::new(HashMap::new()),
+            next_connection_id: AtomicU64::new(1),
+            next_operation_id: AtomicU64::new(1),
         }
     }
 
-    /// Detect if a database is encrypted (SQLCipher) or regular SQLite
-    fn detect_database_type(&self, path: &str) -> anyhow::Result<DatabaseType> {
+    /// Detect if a database is encrypted (SQLCipher) or regular SQLite by inspecting its header,
+    /// without opening (and possibly creating) it through SQLite
+    pub fn detect_database_type(&self, path: &str) -> anyhow::Result<DatabaseType> {
+        use std::io::Read;
+
         // Use Path for cross-platform handling
         let db_path = Path::new(path);
 
-        // Try to open as regular SQLite first
-        match Connection::open(db_path) {
-            Ok(conn) => {
-                // Try to read sqlite_master without password
-                match conn.query_row("SELECT COUNT(*) FROM sqlite_master;", [], |row| row.get::<_, i32>(0)) {
-                    Ok(_) => {
-                        println!("Database detected as: Regular SQLite (unencrypted)");
-                        Ok(DatabaseType::SQLite)
-                    },
-                    Err(_) => {
-                        // If we can't read, it might be encrypted
-                        println!("Database detected as: SQLCipher (encrypted)");
-                        Ok(DatabaseType::SQLCipher)
+        let metadata = match std::fs::metadata(db_path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(DatabaseType::Missing),
+            Err(e) => return Err(anyhow::anyhow!("Failed to read database file metadata: {}", e)),
+        };
+        if !metadata.is_file() {
+            return Ok(DatabaseType::NotADatabase);
+        }
+        if metadata.len() == 0 {
+            return Ok(DatabaseType::Empty);
+        }
+
+        let mut header = Vec::with_capacity(SQLITE_HEADER_SIZE);
+        std::fs::File::open(db_path)
+            .with_context(|| format!("Failed to open database file: {}", path))?
+            .take(SQLITE_HEADER_SIZE as u64)
+            .read_to_end(&mut header)
+            .with_context(|| format!("Failed to read database header: {}", path))?;
+
+        // Anything shorter than a header is truncated or not a database at all
+        if header.len() < SQLITE_HEADER_SIZE {
+            return Ok(DatabaseType::NotADatabase);
+        }
+
+        let db_type = if header.starts_with(SQLITE_MAGIC) {
+            // Plain SQLite requires the "reserved for expansion" bytes 72..92 to be zero;
+            // with a plaintext header only the first 32 bytes are in the clear
+            if header[72..92].iter().all(|&b| b == 0) {
+                DatabaseType::Plain
+            } else {
+                DatabaseType::PlaintextHeaderEncrypted
+            }
+        } else if Self::shannon_entropy(&header) >= ENCRYPTED_ENTROPY_THRESHOLD && metadata.len() % 512 == 0 {
+            // SQLCipher files are whole pages of ciphertext (page sizes are multiples of 512)
+            DatabaseType::Encrypted
+        } else {
+            DatabaseType::NotADatabase
+        };
+
+        log::debug!("Database detected as: {:?}", db_type);
+        Ok(db_type)
+    }
+
+    /// Build a `file:` URI for SQLite, percent-encoding characters that would otherwise be
+    /// read as URI syntax
+    fn sqlite_uri(path: &Path, query: &str) -> String {
+        let raw = path.to_string_lossy().replace('\\', "/");
+        let mut uri = String::from("file:");
+        // Windows drive paths need an empty authority: file:///C:/...
+        if !raw.starts_with('/') {
+            uri.push_str("///");
+        }
+        for c in raw.chars() {
+            match c {
+                '%' | '?' | '#' => uri.push_str(&format!("%{:02X}", c as u32)),
+                _ => uri.push(c),
+            }
+        }
+        uri.push('?');
+        uri.push_str(query);
+        uri
+    }
+
+    /// Open a connection in the requested mode; never creates a missing file
+    fn open_connection(&self, db_path: &Path, mode: OpenMode) -> anyhow::Result<Connection> {
+        let conn = match mode {
+            OpenMode::ReadOnly => Connection::open_with_flags(
+                db_path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            ),
+            // immutable=1 skips locking and change detection entirely, so SQLite never
+            // touches the file or creates -wal/-shm files next to it
+            OpenMode::Immutable => Connection::open_with_flags(
+                Self::sqlite_uri(db_path, "immutable=1"),
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            ),
+            OpenMode::ReadWrite => Connection::open_with_flags(
+                db_path,
+                OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            ),
+        };
+
+        let conn = conn.with_context(|| format!("Failed to open database file: {}", db_path.display()))?;
+        conn.create_aggregate_function(
+            ROW_DIGEST_FUNCTION,
+            -1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            RowDigest,
+        )?;
+        Ok(conn)
+    }
+
+    /// Shannon entropy of a byte slice in bits per byte
+    fn shannon_entropy(bytes: &[u8]) -> f64 {
+        let mut counts = [0usize; 256];
+        for &b in bytes {
+            counts[b as usize] += 1;
+        }
+
+        let len = bytes.len() as f64;
+        counts
+            .iter()
+            .filter(|&&c| c > 0)
+            .map(|&c| {
+                let p = c as f64 / len;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    /// Apply SQLCipher codec pragmas; must run after `PRAGMA key` and before the first read
+    fn apply_cipher_settings(&self, conn: &Connection, settings: &CipherSettings) -> anyhow::Result<()> {
+        // cipher_compatibility resets every other parameter, so it goes first and the
+        // individual overrides are layered on top of it
+        if let Some(compatibility) = settings.cipher_compatibility {
+            if !(1..=4).contains(&compatibility) {
+                return Err(AppError::InvalidInput(format!("Unsupported cipher_compatibility {} (expected 1-4)", compatibility)).into());
+            }
+            conn.pragma_update(None, "cipher_compatibility", compatibility)?;
+        }
+        if let Some(kdf_iter) = settings.kdf_iter {
+            if kdf_iter == 0 {
+                return Err(AppError::InvalidInput("kdf_iter must be greater than 0".to_string()).into());
+            }
+            conn.pragma_update(None, "kdf_iter", kdf_iter)?;
+        }
+        if let Some(page_size) = settings.cipher_page_size {
+            if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+                return Err(AppError::InvalidInput(format!("Invalid cipher_page_size {} (expected a power of two between 512 and 65536)", page_size)).into());
+            }
+            conn.pragma_update(None, "cipher_page_size", page_size)?;
+        }
+        if let Some(use_hmac) = settings.cipher_use_hmac {
+            conn.pragma_update(None, "cipher_use_hmac", use_hmac)?;
+        }
+        if let Some(algorithm) = &settings.cipher_hmac_algorithm {
+            let algorithm = algorithm.to_uppercase();
+            if !["HMAC_SHA1", "HMAC_SHA256", "HMAC_SHA512"].contains(&algorithm.as_str()) {
+                return Err(AppError::InvalidInput(format!("Unsupported cipher_hmac_algorithm: {}", algorithm)).into());
+            }
+            conn.pragma_update(None, "cipher_hmac_algorithm", &algorithm)?;
+        }
+        if let Some(algorithm) = &settings.cipher_kdf_algorithm {
+            let algorithm = algorithm.to_uppercase();
+            if !["PBKDF2_HMAC_SHA1", "PBKDF2_HMAC_SHA256", "PBKDF2_HMAC_SHA512"].contains(&algorithm.as_str()) {
+                return Err(AppError::InvalidInput(format!("Unsupported cipher_kdf_algorithm: {}", algorithm)).into());
+            }
+            conn.pragma_update(None, "cipher_kdf_algorithm", &algorithm)?;
+        }
+        if let Some(header_size) = settings.cipher_plaintext_header_size {
+            if header_size % 16 != 0 {
+                return Err(AppError::InvalidInput(format!("cipher_plaintext_header_size must be a multiple of 16, got {}", header_size)).into());
+            }
+            conn.pragma_update(None, "cipher_plaintext_header_size", header_size)?;
+        }
+        if let Some(salt) = &settings.cipher_salt {
+            // The salt is not stored in the file when the header is left in plaintext
+            conn.pragma_update(None, "cipher_salt", Self::raw_key_literal(salt, 16)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Cipher profiles tried by detection: the four SQLCipher major-version defaults first,
+    /// then variants commonly shipped by apps that tune individual parameters
+    fn cipher_profiles() -> Vec<(&'static str, CipherSettings)> {
+        let compat = |version: u8| CipherSettings {
+            cipher_compatibility: Some(version),
+            ..Default::default()
+        };
+
+        vec![
+            ("sqlcipher4", compat(4)),
+            ("sqlcipher3", compat(3)),
+            ("sqlcipher2", compat(2)),
+            ("sqlcipher1", compat(1)),
+            ("sqlcipher4-kdf64000", CipherSettings { kdf_iter: Some(64000), ..compat(4) }),
+            ("sqlcipher3-kdf256000", CipherSettings { kdf_iter: Some(256000), ..compat(3) }),
+            ("sqlcipher4-page1024", CipherSettings { cipher_page_size: Some(1024), ..compat(4) }),
+            ("sqlcipher3-page4096", CipherSettings { cipher_page_size: Some(4096), ..compat(3) }),
+            ("sqlcipher4-no-hmac", CipherSettings { cipher_use_hmac: Some(false), ..compat(4) }),
+            ("sqlcipher3-no-hmac", CipherSettings { cipher_use_hmac: Some(false), ..compat(3) }),
+            ("sqlcipher4-sha1", CipherSettings {
+                cipher_hmac_algorithm: Some("HMAC_SHA1".to_string()),
+                cipher_kdf_algorithm: Some("PBKDF2_HMAC_SHA1".to_string()),
+                ..compat(4)
+            }),
+            ("sqlcipher4-kdf64000-sha1", CipherSettings {
+                kdf_iter: Some(64000),
+                cipher_hmac_algorithm: Some("HMAC_SHA1".to_string()),
+                cipher_kdf_algorithm: Some("PBKDF2_HMAC_SHA1".to_string()),
+                ..compat(4)
+            }),
+        ]
+    }
+
+    /// Strip surrounding whitespace and an optional `x'...'` wrapper from a hex key
+    fn unwrap_hex_key(hex: &str) -> &str {
+        let hex = hex.trim();
+        hex.strip_prefix("x'")
+            .or_else(|| hex.strip_prefix("X'"))
+            .and_then(|h| h.strip_suffix('\''))
+            .unwrap_or(hex)
+    }
+
+    /// Validate a hex key (or salt) and return it as a SQLCipher raw key literal
+    fn raw_key_literal(hex: &str, expected_bytes: usize) -> anyhow::Result<String> {
+        let hex = Self::unwrap_hex_key(hex);
+
+        if hex.len() != expected_bytes * 2 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(AppError::InvalidInput(format!(
+                "Raw key must be exactly {} hex characters ({} bytes)",
+                expected_bytes * 2,
+                expected_bytes
+            )).into());
+        }
+
+        Ok(format!("x'{}'", hex))
+    }
+
+    /// Turn key material into the value passed to `PRAGMA key`
+    fn key_pragma_value(&self, key: &KeyMaterial) -> anyhow::Result<String> {
+        let value = match key {
+            KeyMaterial::Passphrase { passphrase } => Ok(passphrase.clone()),
+            KeyMaterial::RawKey { key_hex } => Self::raw_key_literal(key_hex, 32),
+            KeyMaterial::RawKeyWithSalt { key_hex, salt_hex } => {
+                Self::raw_key_literal(&format!("{}{}", key_hex.trim(), salt_hex.trim()), 48)
+            }
+            KeyMaterial::KeyFile { path, raw } => {
+                let contents = std::fs::read(path)
+                    .with_context(|| format!("Failed to read key file: {}", path))?;
+
+                if !raw {
+                    let passphrase = String::from_utf8(contents)
+                        .context("Key file is not valid UTF-8 text")?;
+                    Ok(passphrase.trim_end_matches(['\r', '\n']).to_string())
+                } else {
+                    // Raw key files hold either the binary key (+ salt) or its hex encoding
+                    match contents.len() {
+                        32 | 48 => {
+                            let hex: String = contents.iter().map(|b| format!("{:02x}", b)).collect();
+                            Self::raw_key_literal(&hex, contents.len())
+                        }
+                        _ => {
+                            let text = String::from_utf8(contents)
+                                .context("Raw key file must contain 32/48 binary bytes or hex text")?;
+                            let expected_bytes = if Self::unwrap_hex_key(&text).len() == 96 { 48 } else { 32 };
+                            Self::raw_key_literal(&text, expected_bytes)
+                        }
                     }
                 }
-            },
-            Err(e) => {
-                Err(anyhow::anyhow!("Failed to open database file: {}", e))
             }
+        }?;
+
+        // SQLCipher rejects an empty key with a generic SQL error; for an encrypted file
+        // that is simply the wrong key
+        if value.is_empty() {
+            return Err(AppError::WrongKey("The database is encrypted and no key was given".to_string()).into());
+        }
+        Ok(value)
+    }
+
+    /// Try every known cipher profile with the given key and return the first one that opens the file.
+    /// `salt` belongs to the file rather than a profile, so every profile is tried with it; a
+    /// plaintext-header database cannot be opened without it.
+    pub fn detect_cipher_settings(&self, path: &str, key: &KeyMaterial, salt: Option<String>, db_type: DatabaseType, open_mode: OpenMode) -> anyhow::Result<(String, CipherSettings)> {
+        let db_path = Path::new(path);
+        let key_value = self.key_pragma_value(key)?;
+
+        for (name, mut settings) in Self::cipher_profiles() {
+            if db_type == DatabaseType::PlaintextHeaderEncrypted {
+                settings.cipher_plaintext_header_size = Some(PLAINTEXT_HEADER_SIZE);
+            }
+            settings.cipher_salt = salt.clone();
+
+            // Each attempt needs a fresh handle: a failed key leaves the codec unusable.
+            // Probing never needs to write, whatever mode the final connection uses
+            let probe_mode = if open_mode == OpenMode::Immutable { OpenMode::Immutable } else { OpenMode::ReadOnly };
+            let conn = self.open_connection(db_path, probe_mode)?;
+            conn.pragma_update(None, "key", &key_value)?;
+            self.apply_cipher_settings(&conn, &settings)?;
+
+            if conn.query_row("SELECT COUNT(*) FROM sqlite_master;", [], |row| row.get::<_, i32>(0)).is_ok() {
+                log::debug!("Cipher profile detected: {}", name);
+                return Ok((name.to_string(), settings));
+            }
+        }
+
+        Err(AppError::WrongKey("No known cipher profile opened the database; the key may be wrong".to_string()).into())
+    }
+
+    /// Resolve symlinks and relative segments so one file always maps to one path.
+    /// Missing files are left as given so type detection can report them.
+    fn canonical_path(path: &str) -> String {
+        match std::fs::canonicalize(path) {
+            Ok(canonical) => {
+                let canonical = canonical.to_string_lossy().to_string();
+                // Windows returns verbatim paths (\\?\C:\...) that SQLite URIs can't express
+                match canonical.strip_prefix(r"\\?\") {
+                    Some(rest) if rest.as_bytes().get(1) == Some(&b':') => rest.to_string(),
+                    _ => canonical,
+                }
+            }
+            Err(_) => path.to_string(),
         }
     }
 
-    /// Connect to database with automatic type detection
-    pub fn connect_database(&mut self, path: &str, password: &str) -> anyhow::Result<DatabaseInfo> {
-        println!("Attempting to connect to database: {}", path);
+    fn next_connection_id(&self) -> (u64, ConnectionId) {
+        let sequence = self.next_connection_id.fetch_add(1, Ordering::SeqCst);
+        (sequence, ConnectionId(format!("conn-{}", sequence)))
+    }
+
+    /// Connect to database with automatic type detection and return a new handle for it.
+    /// The same file may be connected several times, e.g. with different keys.
+    pub fn connect_database(&self, path: &str, alias: Option<String>, key: &KeyMaterial, cipher_settings: Option<CipherSettings>, detect_cipher: bool, open_mode: OpenMode) -> anyhow::Result<DatabaseInfo> {
+        let (sequence, id) = self.next_connection_id();
+        let (conn, mut db_info) = self.open_database(id, path, key, cipher_settings, detect_cipher, open_mode)?;
+        db_info.alias = alias.filter(|a| !a.trim().is_empty());
+
+        self.connections.lock().unwrap().insert(
+            db_info.id.clone(),
+            Self::wrap_connection(sequence, conn, db_info.clone(), key.clone()),
+        );
+        log::debug!("Database connection stored as {}", db_info.id);
+
+        Ok(db_info)
+    }
+
+    /// Open and verify a connection without registering it
+    fn open_database(&self, id: ConnectionId, path: &str, key: &KeyMaterial, cipher_settings: Option<CipherSettings>, detect_cipher: bool, open_mode: OpenMode) -> anyhow::Result<(Connection, DatabaseInfo)> {
+        log::debug!("Attempting to connect to database: {}", path);
         
         // Use Path for cross-platform compatibility
+        let canonical = Self::canonical_path(path);
+        let path = canonical.as_str();
         let db_path = Path::new(path);
         
         // First detect database type
         let db_type = self.detect_database_type(path)?;
         
         match db_type {
-            DatabaseType::SQLite => {
+            DatabaseType::Missing => {
+                Err(AppError::FileNotFound(path.to_string()).into())
+            }
+            DatabaseType::Empty => {
+                Err(AppError::NotADatabase(format!("Database file is empty: {}", path)).into())
+            }
+            DatabaseType::NotADatabase => {
+                Err(AppError::NotADatabase(path.to_string()).into())
+            }
+            DatabaseType::Plain => {
                 // Handle SQLite connection
-                let conn = Connection::open(db_path)
-                    .with_context(|| format!("Failed to open database file: {}", path))?;
+                let conn = self.open_connection(db_path, open_mode)?;
                 
                 let table_count: i32 = conn.query_row(
                     "SELECT COUNT(*) FROM sqlite_master WHERE type='table';", 
@@ -432,58 +913,56 @@ impl DatabaseManager {
                 )?;
                 
                 let db_info = DatabaseInfo {
+                    id: id.clone(),
                     path: path.to_string(),
                     name: db_path.file_name().unwrap_or_default().to_string_lossy().to_string(),
                     table_count,
                     is_connected: true,
                     alias: None,
+                    cipher_settings: None,
+                    cipher_profile: None,
+                    open_mode,
+                    opened_at: Self::now_millis(),
                 };
                 
-                self.connections.insert(path.to_string(), conn);
-                Ok(db_info)
+                Ok((conn, db_info))
             }
-            DatabaseType::SQLCipher => {
-                // Handle SQLCipher connection with password
-                let conn = Connection::open(db_path)
-                    .with_context(|| format!("Failed to open database file: {}", path))?;
-                
-                let key_formats = vec![
-                    format!("PRAGMA key = '{}';", password),
-                    format!("PRAGMA key = \"{}\";", password),
-                    format!("PRAGMA key = {};", password),
-                    format!("PRAGMA key='{}';", password),
-                ];
-
-                let mut sqlcipher_success = false;
-                let mut last_error = String::new();
-                let mut table_count = 0;
-
-                for key_format in key_formats {
-                    println!("Trying SQLCipher key format: {}", key_format.replace(password, "****"));
-                    
-                    if let Err(e) = conn.execute_batch(&key_format) {
-                        last_error = format!("Key format failed: {}", e);
-                        continue;
-                    }
-                    
-                    // Test if the key worked
-                    match conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type='table';", [], |row| row.get::<_, i32>(0)) {
-                        Ok(count) => {
-                            println!("SQLCipher key accepted! Found {} tables.", count);
-                            sqlcipher_success = true;
-                            table_count = count;
-                            break;
-                        },
-                        Err(e) => {
-                            last_error = format!("Key verification failed: {}", e);
-                            continue;
-                        }
-                    }
-                }
-
-                if !sqlcipher_success {
-                    return Err(anyhow::anyhow!("Failed to connect to SQLCipher database. Last error: {}", last_error));
+            DatabaseType::Encrypted | DatabaseType::PlaintextHeaderEncrypted => {
+                // Handle SQLCipher connection with the supplied key
+                let (mut cipher_settings, cipher_profile) = if detect_cipher {
+                    let salt = cipher_settings.and_then(|s| s.cipher_salt);
+                    let (name, settings) = self.detect_cipher_settings(path, key, salt, db_type, open_mode)?;
+                    (settings, Some(name))
+                } else {
+                    (cipher_settings.unwrap_or_default(), None)
+                };
+                if db_type == DatabaseType::PlaintextHeaderEncrypted && cipher_settings.cipher_plaintext_header_size.is_none() {
+                    cipher_settings.cipher_plaintext_header_size = Some(PLAINTEXT_HEADER_SIZE);
                 }
+                let conn = self.open_connection(db_path, open_mode)?;
+                
+                // pragma_update quotes the value, so passphrases containing quotes and
+                // x'...' raw keys both reach SQLCipher unmodified
+                let key_value = self.key_pragma_value(key)?;
+                conn.pragma_update(None, "key", &key_value)
+                    .context("Failed to set SQLCipher key")?;
+
+                self.apply_cipher_settings(&conn, &cipher_settings)
+                    .context("Failed to apply cipher settings")?;
+
+                // Test if the key worked
+                let table_count: i32 = conn
+                    .query_row("SELECT COUNT(*) FROM sqlite_master WHERE type='table';", [], |row| row.get(0))
+                    .map_err(|e| match e.sqlite_error_code() {
+                        // SQLCipher reports a wrong key (or wrong cipher settings) as "file is not a database"
+                        Some(rusqlite::ErrorCode::NotADatabase) => AppError::WrongKey(format!("Key verification failed for {}", path)).into(),
+                        // With a plaintext header it reports a plain SQL error instead, also when the salt is missing
+                        Some(rusqlite::ErrorCode::Unknown) if db_type == DatabaseType::PlaintextHeaderEncrypted => AppError::WrongKey(
+                            format!("Key verification failed for {}; plaintext-header databases also need the cipher salt", path)
+                        ).into(),
+                        _ => anyhow::Error::new(e).context("Key verification failed"),
+                    })?;
+                log::debug!("SQLCipher key accepted, found {} tables", table_count);
 
                 let db_name = db_path
                     .file_name()
@@ -492,239 +971,2064 @@ impl DatabaseManager {
                     .to_string();
 
                 let db_info = DatabaseInfo {
+                    id: id.clone(),
                     path: path.to_string(),
                     name: db_name,
                     table_count,
                     is_connected: true,
                     alias: None,
+                    cipher_settings: Some(cipher_settings),
+                    cipher_profile,
+                    open_mode,
+                    opened_at: Self::now_millis(),
                 };
 
-                self.connections.insert(path.to_string(), conn);
-                println!("Database connection stored successfully");
-                
-                Ok(db_info)
+                Ok((conn, db_info))
             }
         }
     }   
 
-    pub fn get_tables(&self, db_path: &str) -> anyhow::Result<Vec<TableInfo>> {
-        let conn = self.connections.get(db_path)
-            .context("Database not connected")?;
+    fn now_millis() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
 
-        let mut stmt = conn.prepare(
-            "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
-        )?;
+    fn wrap_connection(sequence: u64, conn: Connection, info: DatabaseInfo, key: KeyMaterial) -> Arc<OpenDatabase> {
+        Arc::new(OpenDatabase {
+            sequence,
+            interrupt: conn.get_interrupt_handle(),
+            conn: Mutex::new(conn),
+            holder: Mutex::new(None),
+            info,
+            key,
+        })
+    }
 
-        let table_names: Vec<String> = stmt.query_map([], |row| {
-            Ok(row.get::<_, String>(0)?)
-        })?.collect::<RusqliteResult<Vec<_>>>()?;
+    /// Close a handle that has been removed from the map. If an operation still holds it,
+    /// the connection closes when that operation finishes.
+    fn close_database(db: Arc<OpenDatabase>) -> anyhow::Result<()> {
+        match Arc::try_unwrap(db) {
+            Ok(db) => db.conn.into_inner().unwrap().close()
+                .map_err(|(_, e)| anyhow::anyhow!("Failed to close database {}: {}", db.info.path, e)),
+            Err(db) => {
+                log::warn!("Database {} is busy; it will close when its running operation finishes", db.info.id);
+                Ok(())
+            }
+        }
+    }
 
-        let mut tables = Vec::new();
-        
-        for table_name in table_names {
-            // Get row count safely
-            let row_count: i64 = conn.query_row(
-                &format!("SELECT COUNT(*) FROM \"{}\"", table_name),
-                [],
-                |row| row.get(0),
-            ).unwrap_or(0);
+    fn database(&self, id: &ConnectionId) -> anyhow::Result<Arc<OpenDatabase>> {
+        self.connections.lock().unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| AppError::NotConnected(id.to_string()).into())
+    }
 
-            // Get column info
-            let columns = self.get_table_columns(conn, &table_name)?;
+    /// Run work against one connection, holding only that connection's lock
+    fn with_connection<T>(&self, id: &ConnectionId, cancel: &CancelToken, work: impl FnOnce(&Connection) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        let db = self.database(id)?;
+        let conn = db.hold(cancel);
+        // The operation may have been cancelled while it waited for the connection
+        cancel.check()?;
 
-            tables.push(TableInfo {
-                name: table_name,
-                row_count,
-                columns,
-            });
+        work(&conn).map_err(|e| {
+            if cancel.is_cancelled() {
+                AppError::Cancelled.into()
+            } else {
+                e
+            }
+        })
+    }
+
+    /// Run work against two different connections at once. Locks are always taken in
+    /// connection order so two operations on the same pair can never deadlock.
+    fn with_connections<T>(
+        &self,
+        id1: &ConnectionId,
+        id2: &ConnectionId,
+        cancel: &CancelToken,
+        work: impl FnOnce(&Connection, &Connection) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        if id1 == id2 {
+            return Err(AppError::InvalidInput(format!("Cannot compare connection {} with itself", id1)).into());
         }
+        let (db1, db2) = (self.database(id1)?, self.database(id2)?);
+        let in_order = db1.sequence <= db2.sequence;
+        let (first, second) = if in_order { (&db1, &db2) } else { (&db2, &db1) };
+        let first = first.hold(cancel);
+        let second = second.hold(cancel);
+        cancel.check()?;
+
+        let (conn1, conn2) = if in_order { (&*first, &*second) } else { (&*second, &*first) };
+        work(conn1, conn2).map_err(|e| {
+            if cancel.is_cancelled() {
+                AppError::Cancelled.into()
+            } else {
+                e
+            }
+        })
+    }
 
-        Ok(tables)
+    fn connection_info(&self, id: &ConnectionId) -> anyhow::Result<DatabaseInfo> {
+        Ok(self.database(id)?.info.clone())
     }
 
-    fn get_table_columns(&self, conn: &Connection, table_name: &str) -> anyhow::Result<Vec<ColumnInfo>> {
-        let mut stmt = conn.prepare(&format!("PRAGMA table_info(\"{}\")", table_name))?;
-        
-        let columns: Vec<ColumnInfo> = stmt.query_map([], |row| {
-            Ok(ColumnInfo {
-                name: row.get::<_, String>(1)?,
-                data_type: row.get::<_, String>(2)?,
-                is_nullable: row.get::<_, i32>(3)? == 0,
-                default_value: row.get::<_, Option<String>>(4)?,
-                is_primary_key: row.get::<_, i32>(5)? == 1,
-            })
-        })?.collect::<RusqliteResult<Vec<_>>>()?;
+    /// Close a connection and release its file lock, interrupting anything running on it
+    pub fn disconnect_database(&self, id: &ConnectionId) -> anyhow::Result<DatabaseInfo> {
+        let db = self.connections.lock().unwrap().remove(id)
+            .ok_or_else(|| AppError::NotConnected(id.to_string()))?;
 
-        Ok(columns)
+        let mut info = db.info.clone();
+        info.is_connected = false;
+        db.interrupt.interrupt();
+        Self::close_database(db)?;
+
+        Ok(info)
     }
 
-    pub fn get_table_data(&self, db_path: &str, table_name: &str, limit: Option<i64>) -> anyhow::Result<TableData> {
-        let conn = self.connections.get(db_path)
-            .context("Database not connected")?;
+    /// Close every open connection, returning the ones that were closed
+    pub fn disconnect_all(&self) -> anyhow::Result<Vec<DatabaseInfo>> {
+        let ids: Vec<ConnectionId> = self.connections.lock().unwrap().keys().cloned().collect();
 
-        // Get column names
-        let columns = self.get_table_columns(conn, table_name)?;
-        let column_names: Vec<String> = columns.iter().map(|c| c.name.clone()).collect();
+        let mut closed = Vec::new();
+        let mut errors = Vec::new();
+        for id in ids {
+            match self.disconnect_database(&id) {
+                Ok(info) => closed.push(info),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
 
-        // Get total count
-        let total_count: i64 = conn.query_row(
-            &format!("SELECT COUNT(*) FROM \"{}\"", table_name),
-            [],
-            |row| row.get(0),
-        )?;
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!("Failed to close {} connection(s): {}", errors.len(), errors.join("; ")));
+        }
 
-        // Build dynamic SELECT query using column names to ensure order
-        let col_list = column_names.iter()
-            .map(|c| format!("\"{}\"", c))
-            .collect::<Vec<_>>()
-            .join(", ");
-        let query = match limit {
-            Some(limit) => format!("SELECT {} FROM \"{}\" LIMIT {}", col_list, table_name, limit),
-            None => format!("SELECT {} FROM \"{}\"", col_list, table_name),
-        };
+        Ok(closed)
+    }
 
-        let mut stmt = conn.prepare(&query)?;
-        let column_count = stmt.column_count();
-        
-        // Return rows as arrays (your existing format)
-        let rows: Vec<Vec<serde_json::Value>> = stmt.query_map([], |row| {
-            let mut row_data = Vec::new();
-            for i in 0..column_count {
-                let value: Value = row.get(i)?;
-                let json_value = match value {
-                    Value::Null => serde_json::Value::Null,
-                    Value::Integer(i) => serde_json::Value::Number(i.into()),
-                    Value::Real(f) => serde_json::Value::Number(
-                        serde_json::Number::from_f64(f).unwrap_or(0.into())
-                    ),
-                    Value::Text(s) => serde_json::Value::String(s),
-                    Value::Blob(b) => serde_json::Value::String(
-                        format!("<BLOB {} bytes>", b.len())
-                    ),
-                };
-                row_data.push(json_value);
+    /// Describe every open connection in the order they were connected
+    pub fn list_connections(&self) -> Vec<DatabaseInfo> {
+        let mut databases: Vec<Arc<OpenDatabase>> = self.connections.lock().unwrap().values().cloned().collect();
+        databases.sort_by_key(|db| db.sequence);
+        databases.iter().map(|db| db.info.clone()).collect()
+    }
+
+    /// Reopen a connection under the same id with the key and cipher settings it was opened
+    /// with, optionally switching the open mode. The old handle stays open if reopening fails.
+    pub fn reconnect_database(&self, id: &ConnectionId, open_mode: Option<OpenMode>) -> anyhow::Result<DatabaseInfo> {
+        let db = self.database(id)?;
+        let previous = db.info.clone();
+        let key = db.key.clone();
+        let sequence = db.sequence;
+        drop(db);
+
+        let (conn, mut db_info) = self.open_database(
+            id.clone(),
+            &previous.path,
+            &key,
+            previous.cipher_settings.clone(),
+            false,
+            open_mode.unwrap_or(previous.open_mode),
+        )?;
+        // Detection is not re-run on reconnect, so carry the profile name over
+        db_info.alias = previous.alias;
+        db_info.cipher_profile = previous.cipher_profile;
+
+        let old = self.connections.lock().unwrap().insert(
+            id.clone(),
+            Self::wrap_connection(sequence, conn, db_info.clone(), key),
+        );
+        if let Some(old) = old {
+            if let Err(e) = Self::close_database(old) {
+                log::warn!("Failed to close previous handle for {}: {}", id, e);
             }
-            Ok(row_data)
-        })?.collect::<RusqliteResult<Vec<_>>>()?;
+        }
 
-        Ok(TableData {
-            columns: column_names,
-            rows,
-            total_count,
-        })
+        Ok(db_info)
     }
 
-    pub fn compare_schemas(&self, db1_path: &str, db2_path: &str) -> anyhow::Result<SchemaComparison> {
-        let tables1 = self.get_tables(db1_path)?;
-        let tables2 = self.get_tables(db2_path)?;
+    pub fn next_operation_id(&self) -> String {
+        format!("op-{}", self.next_operation_id.fetch_add(1, Ordering::SeqCst))
+    }
 
-        let table1_names: std::collections::HashSet<String> = tables1.iter().map(|t| t.name.clone()).collect();
-        let table2_names: std::collections::HashSet<String> = tables2.iter().map(|t| t.name.clone()).collect();
+    /// Register an operation touching the given connections so it can be cancelled by id
+    pub fn begin_operation(&self, operation_id: &str, ids: &[ConnectionId], progress: Option<ProgressSink>) -> anyhow::Result<CancelToken> {
+        let databases = ids.iter()
+            .map(|id| self.database(id))
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
-        let added_tables: Vec<String> = table2_names.difference(&table1_names).cloned().collect();
-        let removed_tables: Vec<String> = table1_names.difference(&table2_names).cloned().collect();
-        
-        let common_tables: Vec<String> = table1_names.intersection(&table2_names).cloned().collect();
-        let mut modified_tables = Vec::new();
-        let mut identical_tables = Vec::new();
+        let mut operations = self.operations.lock().unwrap();
+        if operations.contains_key(operation_id) {
+            return Err(AppError::InvalidInput(format!("Operation id already in use: {}", operation_id)).into());
+        }
 
-        for table_name in common_tables {
-            let table1 = tables1.iter().find(|t| t.name == table_name).unwrap();
-            let table2 = tables2.iter().find(|t| t.name == table_name).unwrap();
-            
-            if self.tables_are_identical(&table1, &table2) {
-                identical_tables.push(table_name);
-            } else {
-                let diff = self.compare_tables(&table1, &table2);
-                modified_tables.push(diff);
+        let cancel = match progress {
+            Some(sink) => CancelToken::with_progress(operation_id, sink),
+            None => CancelToken::new(),
+        };
+        operations.insert(operation_id.to_string(), RunningOperation {
+            cancel: cancel.clone(),
+            databases,
+        });
+        Ok(cancel)
+    }
+
+    pub fn end_operation(&self, operation_id: &str) {
+        self.operations.lock().unwrap().remove(operation_id);
+    }
+
+    /// Cancel a running operation: its remaining statements fail fast and the statement it is
+    /// executing is interrupted. Connections it is still waiting for are left alone, since
+    /// another operation is running on them. Returns false if no operation with that id is running.
+    pub fn cancel_operation(&self, operation_id: &str) -> bool {
+        let operations = self.operations.lock().unwrap();
+        match operations.get(operation_id) {
+            Some(operation) => {
+                operation.cancel.cancel();
+                for db in &operation.databases {
+                    db.interrupt_held_by(&operation.cancel);
+                }
+                true
             }
+            None => false,
         }
+    }
+
+    pub fn get_tables(&self, id: &ConnectionId, cancel: &CancelToken) -> anyhow::Result<Vec<TableInfo>> {
+        self.with_connection(id, cancel, |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
+            )?;
 
-        Ok(SchemaComparison {
-            database1: db1_path.to_string(),
-            database2: db2_path.to_string(),
-            added_tables,
-            removed_tables,
-            modified_tables,
-            identical_tables,
+            let table_names: Vec<String> = stmt.query_map([], |row| row.get::<_, String>(0))?
+                .collect::<RusqliteResult<Vec<_>>>()?;
+
+            let mut tables = Vec::new();
+            let total = table_names.len() as u64;
+        
+            for (done, table_name) in table_names.into_iter().enumerate() {
+                cancel.check()?;
+                cancel.report(ProgressPhase::LoadingTables, Some(id), Some(&table_name), done as u64, total);
+
+                // Get row count safely
+                let row_count: i64 = conn.query_row(
+                    &format!("SELECT COUNT(*) FROM {}", Self::quote_identifier(&table_name)),
+                    [],
+                    |row| row.get(0),
+                ).unwrap_or(0);
+
+                // Get column info
+                let columns = self.get_table_columns(conn, &table_name)?;
+                let primary_key = Self::primary_key_columns(conn, &table_name)?;
+                let foreign_keys = Self::foreign_keys(conn, &table_name)?;
+                let unique_constraints = Self::unique_constraints(conn, &table_name)?;
+                let definition = Self::table_definition(conn, &table_name)?;
+                let checks = definition.as_ref().map(TableDefinition::constraint_checks).unwrap_or_default();
+                let autoincrement = definition.as_ref()
+                    .is_some_and(|d| d.items.iter().any(|item| has_keywords(item, &["autoincrement"])));
+                let (without_rowid, strict) = conn.query_row(
+                    "SELECT wr, strict FROM pragma_table_list(?1) WHERE schema = 'main'",
+                    [&table_name],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                ).optional()?.unwrap_or_default();
+
+                tables.push(TableInfo {
+                    name: table_name,
+                    row_count,
+                    columns,
+                    primary_key,
+                    foreign_keys,
+                    unique_constraints,
+                    checks,
+                    autoincrement,
+                    without_rowid,
+                    strict,
+                });
+            }
+            cancel.report(ProgressPhase::LoadingTables, Some(id), None, total, total);
+
+            Ok(tables)
         })
     }
 
-    fn tables_are_identical(&self, table1: &TableInfo, table2: &TableInfo) -> bool {
-        if table1.columns.len() != table2.columns.len() {
-            return false;
-        }
+    /// Tables plus the indexes, triggers and views defined on them, and the schema pragmas
+    pub fn get_schema(&self, id: &ConnectionId, cancel: &CancelToken) -> anyhow::Result<DatabaseSchema> {
+        let tables = self.get_tables(id, cancel)?;
+        self.with_connection(id, cancel, |conn| {
+            Ok(DatabaseSchema {
+                tables,
+                indexes: Self::get_indexes(conn)?,
+                triggers: Self::get_triggers(conn)?,
+                views: Self::get_views(conn)?,
+                pragmas: Self::get_pragmas(conn)?,
+            })
+        })
+    }
 
-        for (col1, col2) in table1.columns.iter().zip(table2.columns.iter()) {
-            if col1.name != col2.name || 
-               col1.data_type != col2.data_type || 
-               col1.is_nullable != col2.is_nullable ||
-               col1.is_primary_key != col2.is_primary_key {
-                return false;
-            }
+    fn get_pragmas(conn: &Connection) -> anyhow::Result<SchemaPragmas> {
+        Ok(SchemaPragmas {
+            user_version: conn.query_row("PRAGMA user_version", [], |row| row.get(0))?,
+            application_id: conn.query_row("PRAGMA application_id", [], |row| row.get(0))?,
+            encoding: conn.query_row("PRAGMA encoding", [], |row| row.get(0))?,
+            auto_vacuum: conn.query_row("PRAGMA auto_vacuum", [], |row| row.get(0))?,
+        })
+    }
+
+    fn get_indexes(conn: &Connection) -> anyhow::Result<Vec<IndexInfo>> {
+        // Automatic indexes (UNIQUE/PRIMARY KEY constraints) have no SQL of their own
+        let mut stmt = conn.prepare("SELECT name, tbl_name, sql FROM sqlite_master WHERE type = 'index' AND sql IS NOT NULL ORDER BY name")?;
+        let definitions = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?.collect::<RusqliteResult<Vec<_>>>()?;
+
+        let mut indexes = Vec::with_capacity(definitions.len());
+        for (name, table_name, sql) in definitions {
+            let is_unique: bool = conn.query_row(
+                "SELECT \"unique\" FROM pragma_index_list(?1) WHERE name = ?2",
+                [&table_name, &name],
+                |row| row.get(0),
+            )?;
+            let (terms, where_clause) = index_terms(&sql);
+            let mut stmt = conn.prepare("SELECT seqno, cid, name, \"desc\", coll FROM pragma_index_xinfo(?1) WHERE key = 1 ORDER BY seqno")?;
+            let columns = stmt.query_map([&name], |row| {
+                let seqno: usize = row.get(0)?;
+                let cid: i64 = row.get(1)?;
+                Ok(IndexColumn {
+                    name: row.get(2)?,
+                    // `cid` is -2 for expressions, which only the CREATE INDEX statement spells out
+                    expression: if cid == -2 { terms.get(seqno).cloned() } else { None },
+                    descending: row.get(3)?,
+                    collation: row.get(4)?,
+                })
+            })?.collect::<RusqliteResult<Vec<_>>>()?;
+
+            indexes.push(IndexInfo { name, table_name, is_unique, columns, where_clause, sql });
         }
+        Ok(indexes)
+    }
 
-        true
+    fn get_triggers(conn: &Connection) -> anyhow::Result<Vec<TriggerInfo>> {
+        let mut stmt = conn.prepare("SELECT name, tbl_name, sql FROM sqlite_master WHERE type = 'trigger' ORDER BY name")?;
+        let triggers = stmt.query_map([], |row| {
+            Ok(TriggerInfo { name: row.get(0)?, table_name: row.get(1)?, sql: row.get(2)? })
+        })?.collect::<RusqliteResult<Vec<_>>>()?;
+        Ok(triggers)
     }
 
-    fn compare_tables(&self, table1: &TableInfo, table2: &TableInfo) -> TableDiff 
-    {
-        let col1_names: std::collections::HashSet<String> = table1.columns.iter().map(|c| c.name.clone()).collect();
-        let col2_names: std::collections::HashSet<String> = table2.columns.iter().map(|c| c.name.clone()).collect();
+    fn get_views(conn: &Connection) -> anyhow::Result<Vec<ViewInfo>> {
+        let mut stmt = conn.prepare("SELECT name, sql FROM sqlite_master WHERE type = 'view' ORDER BY name")?;
+        let definitions = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?.collect::<RusqliteResult<Vec<_>>>()?;
 
-        let added_columns: Vec<ColumnInfo> = table2.columns.iter()
-            .filter(|c| !col1_names.contains(&c.name))
-            .cloned()
-            .collect();
+        let mut views = Vec::with_capacity(definitions.len());
+        for (name, sql) in definitions {
+            // A view over a table that no longer exists can't report its columns; that
+            // shouldn't stop the rest of the schema from loading
+            let columns = conn.prepare("SELECT name FROM pragma_table_info(?1)")
+                .and_then(|mut stmt| stmt.query_map([&name], |row| row.get(0))?.collect::<RusqliteResult<Vec<String>>>())
+                .unwrap_or_default();
+            views.push(ViewInfo { name, columns, sql });
+        }
+        Ok(views)
+    }
 
-        let removed_columns: Vec<String> = col1_names.difference(&col2_names)
-            .cloned()
-            .collect();
+    fn get_table_columns(&self, conn: &Connection, table_name: &str) -> anyhow::Result<Vec<ColumnInfo>> {
+        // COLLATE, CHECK and generation expressions only exist in the CREATE statement
+        let definition = Self::table_definition(conn, table_name)?;
+        // `hidden` is 1 for hidden columns of virtual tables, 2 and 3 for VIRTUAL and STORED
+        // generated columns
+        let mut stmt = conn.prepare(
+            "SELECT name, type, \"notnull\", dflt_value, pk, hidden FROM pragma_table_xinfo(?1) WHERE hidden <> 1 ORDER BY cid"
+        )?;
 
-        // Build name->ColumnInfo maps
-        let map1: std::collections::HashMap<_, _> = table1.columns.iter().map(|c| (c.name.clone(), c)).collect();
-        let map2: std::collections::HashMap<_, _> = table2.columns.iter().map(|c| (c.name.clone(), c)).collect();
+        let columns: Vec<ColumnInfo> = stmt.query_map([table_name], |row| {
+            let name = row.get::<_, String>(0)?;
+            let hidden = row.get::<_, i32>(5)?;
+            let clauses = definition.as_ref()
+                .and_then(|d| d.column(&name))
+                .map(|column| DefinitionClauses::parse(column, 1))
+                .unwrap_or_default();
+            let data_type = row.get::<_, String>(1)?;
+            Ok(ColumnInfo {
+                affinity: TypeAffinity::of(&data_type),
+                data_type,
+                is_nullable: row.get::<_, i32>(2)? == 0,
+                default_value: row.get::<_, Option<String>>(3)?,
+                // `pk` is the column's 1-based position in the primary key, 0 if not part of it
+                is_primary_key: row.get::<_, i32>(4)? > 0,
+                collation: clauses.collation,
+                generated: (hidden >= 2).then(|| GeneratedColumn {
+                    expression: clauses.generated.unwrap_or_default(),
+                    stored: hidden == 3,
+                }),
+                checks: clauses.checks,
+                name,
+            })
+        })?.collect::<RusqliteResult<Vec<_>>>()?;
 
-        // Columns that exist in both
-        let common_cols: Vec<String> = col1_names.intersection(&col2_names).cloned().collect();
+        Ok(columns)
+    }
 
-        let mut modified_columns = Vec::new();
-        for name in common_cols {
-            let c1 = map1.get(&name).unwrap();
-            let c2 = map2.get(&name).unwrap();
+    /// The parsed `CREATE TABLE` of a table; `None` for views and virtual tables
+    fn table_definition(conn: &Connection, table_name: &str) -> anyhow::Result<Option<TableDefinition>> {
+        let sql: Option<String> = conn.query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [table_name],
+            |row| row.get(0),
+        ).optional()?.flatten();
+        Ok(sql.as_deref().and_then(TableDefinition::parse))
+    }
 
-            let mut changes = Vec::new();
+    /// Column lists of the UNIQUE constraints, from the indexes SQLite creates for them
+    fn unique_constraints(conn: &Connection, table_name: &str) -> anyhow::Result<Vec<Vec<String>>> {
+        let mut stmt = conn.prepare("SELECT name FROM pragma_index_list(?1) WHERE origin = 'u'")?;
+        let indexes = stmt.query_map([table_name], |row| row.get::<_, String>(0))?.collect::<RusqliteResult<Vec<_>>>()?;
+        let mut constraints = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            let mut stmt = conn.prepare("SELECT name FROM pragma_index_info(?1) ORDER BY seqno")?;
+            constraints.push(stmt.query_map([&index], |row| row.get::<_, String>(0))?.collect::<RusqliteResult<Vec<_>>>()?);
+        }
+        constraints.sort();
+        Ok(constraints)
+    }
 
-            if c1.data_type.to_lowercase() != c2.data_type.to_lowercase() {
-                changes.push(format!("type: {} -> {}", c1.data_type, c2.data_type));
-            }
-            if c1.is_nullable != c2.is_nullable {
-                changes.push(format!("nullability: {} -> {}", c1.is_nullable, c2.is_nullable));
-            }
-            if c1.is_primary_key != c2.is_primary_key {
-                changes.push(format!("primary key: {} -> {}", c1.is_primary_key, c2.is_primary_key));
-            }
-            if c1.default_value != c2.default_value {
-                changes.push("default changed".to_string());
-            }
+    /// Find a table or view the way SQL does, ignoring ASCII case, and return its name as stored
+    fn resolve_table_name(conn: &Connection, table_name: &str) -> anyhow::Result<String> {
+        conn.query_row(
+            "SELECT name FROM sqlite_master WHERE type IN ('table', 'view') AND name = ?1 COLLATE NOCASE",
+            [table_name],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| AppError::TableNotFound(table_name.to_string()).into())
+    }
 
-            if !changes.is_empty() {
-                modified_columns.push(ColumnDiff{
-                    column_name: name,
-                    old_type: c1.data_type.clone(),
-                    new_type: c2.data_type.clone(),
-                    changes,
-                });
-            }
+    fn value_to_json(value: &Value) -> serde_json::Value {
+        match value {
+            Value::Null => serde_json::Value::Null,
+            Value::Integer(i) => serde_json::Value::Number((*i).into()),
+            Value::Real(f) => serde_json::Value::Number(
+                serde_json::Number::from_f64(*f).unwrap_or(0.into())
+            ),
+            Value::Text(s) => serde_json::Value::String(s.clone()),
+            Value::Blob(b) => serde_json::Value::String(
+                format!("<BLOB {} bytes>", b.len())
+            ),
         }
+    }
 
+    pub fn get_table_data(&self, id: &ConnectionId, table_name: &str, limit: Option<i64>, cancel: &CancelToken) -> anyhow::Result<TableData> {
+        self.with_connection(id, cancel, |conn| {
+            let table_name = &Self::resolve_table_name(conn, table_name)?;
 
-        TableDiff {
-            table_name: table1.name.clone(),
-            added_columns,
-            removed_columns,
-            modified_columns,
-        }
+            // Get column names
+            let columns = self.get_table_columns(conn, table_name)?;
+            let column_names: Vec<String> = columns.iter().map(|c| c.name.clone()).collect();
+
+            // Get total count
+            let total_count: i64 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM {}", Self::quote_identifier(table_name)),
+                [],
+                |row| row.get(0),
+            )?;
+
+            // Build dynamic SELECT query using column names to ensure order
+            let col_list = column_names.iter()
+                .map(|c| Self::quote_identifier(c))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let query = match limit {
+                Some(limit) => format!("SELECT {} FROM {} LIMIT {}", col_list, Self::quote_identifier(table_name), limit),
+                None => format!("SELECT {} FROM {}", col_list, Self::quote_identifier(table_name)),
+            };
+
+            let mut stmt = conn.prepare(&query)?;
+            let column_count = stmt.column_count();
+
+            let expected = match limit {
+                Some(limit) if limit >= 0 => total_count.min(limit),
+                _ => total_count,
+            } as u64;
+            let mut loaded = 0u64;
+            cancel.report(ProgressPhase::LoadingRows, Some(id), Some(table_name), 0, expected);
+        
+            // Return rows as arrays (your existing format)
+            let rows: Vec<Vec<serde_json::Value>> = stmt.query_map([], |row| {
+                loaded += 1;
+                if loaded.is_multiple_of(ROW_PROGRESS_INTERVAL) {
+                    cancel.report(ProgressPhase::LoadingRows, Some(id), Some(table_name), loaded, expected);
+                }
+
+                let mut row_data = Vec::new();
+                for i in 0..column_count {
+                    let value: Value = row.get(i)?;
+                    row_data.push(Self::value_to_json(&value));
+                }
+                Ok(row_data)
+            })?.collect::<RusqliteResult<Vec<_>>>()?;
+            cancel.report(ProgressPhase::LoadingRows, Some(id), Some(table_name), rows.len() as u64, expected);
+
+            Ok(TableData {
+                columns: column_names,
+                rows,
+                total_count,
+            })
+        })
     }
-    
-}
\ No newline at end of file
+
+    pub fn compare_schemas(&self, db1: &ConnectionId, db2: &ConnectionId, options: &SchemaCompareOptions, cancel: &CancelToken) -> anyhow::Result<SchemaComparison> {
+        let schema1 = self.get_schema(db1, cancel)?;
+        let schema2 = self.get_schema(db2, cancel)?;
+        let databases = (self.connection_info(db1)?.path, self.connection_info(db2)?.path);
+        Ok(Self::compare_database_schemas(&schema1, &schema2, databases, options, cancel))
+    }
+
+    /// Save the schema of `id` as a JSON snapshot at `path`, replacing any existing file
+    pub fn save_schema_snapshot(&self, id: &ConnectionId, path: &str, cancel: &CancelToken) -> anyhow::Result<SchemaSnapshot> {
+        let snapshot = SchemaSnapshot {
+            format_version: SchemaSnapshot::FORMAT_VERSION,
+            source: self.connection_info(id)?.path,
+            created_at: Self::now_millis(),
+            schema: self.get_schema(id, cancel)?,
+        };
+        let json = serde_json::to_string_pretty(&snapshot)?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write schema snapshot: {}", path))?;
+        Ok(snapshot)
+    }
+
+    pub fn load_schema_snapshot(path: &str) -> anyhow::Result<SchemaSnapshot> {
+        let json = std::fs::read_to_string(path).with_context(|| format!("Failed to read schema snapshot: {}", path))?;
+        let not_a_snapshot = |reason: String| AppError::InvalidInput(format!("{} is not a schema snapshot: {}", path, reason));
+
+        // Check the version first so a newer format is refused with a clear message rather
+        // than whatever field it renamed
+        let value: serde_json::Value = serde_json::from_str(&json).map_err(|e| not_a_snapshot(e.to_string()))?;
+        let Some(version) = value.get("format_version").and_then(|v| v.as_u64()) else {
+            return Err(not_a_snapshot("format_version is missing".to_string()).into());
+        };
+        if version > SchemaSnapshot::FORMAT_VERSION as u64 {
+            return Err(AppError::InvalidInput(format!(
+                "Schema snapshot {} has format version {}; this version reads up to {}",
+                path, version, SchemaSnapshot::FORMAT_VERSION
+            )).into());
+        }
+        Ok(serde_json::from_value(value).map_err(|e| not_a_snapshot(e.to_string()))?)
+    }
+
+    /// Compare a connection with a snapshot saved by `save_schema_snapshot`. The connection is
+    /// database1 and the snapshot database2, so the result reads as what the connection lacks
+    /// or has in excess compared to the snapshot.
+    pub fn compare_schema_with_snapshot(&self, id: &ConnectionId, snapshot_path: &str, options: &SchemaCompareOptions, cancel: &CancelToken) -> anyhow::Result<SchemaComparison> {
+        let snapshot = Self::load_schema_snapshot(snapshot_path)?;
+        let schema = self.get_schema(id, cancel)?;
+        let databases = (self.connection_info(id)?.path, snapshot_path.to_string());
+        Ok(Self::compare_database_schemas(&schema, &snapshot.schema, databases, options, cancel))
+    }
+
+    fn compare_database_schemas(
+        schema1: &DatabaseSchema,
+        schema2: &DatabaseSchema,
+        databases: (String, String),
+        options: &SchemaCompareOptions,
+        cancel: &CancelToken,
+    ) -> SchemaComparison {
+        let total = schema1.tables.iter().filter(|t| schema2.tables.iter().any(|other| other.name == t.name)).count() as u64;
+        let mut done = 0;
+        let comparison = diff::compare_schemas(schema1, schema2, databases, options, |table_name| {
+            cancel.report(ProgressPhase::ComparingTables, None, Some(table_name), done, total);
+            done += 1;
+        });
+        cancel.report(ProgressPhase::ComparingTables, None, None, total, total);
+        comparison
+    }
+
+    /// Declared primary key columns in key order (`pk` index of `table_info`)
+    fn primary_key_columns(conn: &Connection, table_name: &str) -> anyhow::Result<Vec<String>> {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", Self::quote_identifier(table_name)))?;
+        let mut keyed: Vec<(i32, String)> = stmt.query_map([], |row| {
+            Ok((row.get::<_, i32>(5)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<RusqliteResult<Vec<_>>>()?
+        .into_iter()
+        .filter(|(pk, _)| *pk > 0)
+        .collect();
+        keyed.sort();
+        Ok(keyed.into_iter().map(|(_, name)| name).collect())
+    }
+
+    /// Foreign keys in declaration order. `PRAGMA foreign_key_list` leaves out deferrability,
+    /// so that is read from the `REFERENCES` clauses of the stored `CREATE TABLE`.
+    fn foreign_keys(conn: &Connection, table_name: &str) -> anyhow::Result<Vec<ForeignKeyInfo>> {
+        // Keys are numbered from the last one declared
+        let mut stmt = conn.prepare(
+            "SELECT id, \"table\", \"from\", \"to\", on_update, on_delete FROM pragma_foreign_key_list(?1) ORDER BY id DESC, seq"
+        )?;
+        let rows = stmt.query_map([table_name], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+            ))
+        })?.collect::<RusqliteResult<Vec<_>>>()?;
+
+        let mut keys: Vec<ForeignKeyInfo> = Vec::new();
+        let mut current_id = None;
+        for (id, referenced_table, from, to, on_update, on_delete) in rows {
+            if current_id != Some(id) {
+                current_id = Some(id);
+                keys.push(ForeignKeyInfo {
+                    columns: Vec::new(),
+                    referenced_table,
+                    referenced_columns: Vec::new(),
+                    on_update,
+                    on_delete,
+                    deferrable: false,
+                    initially_deferred: false,
+                });
+            }
+            if let Some(key) = keys.last_mut() {
+                key.columns.push(from);
+                // `to` is NULL when the key refers to the parent's primary key
+                key.referenced_columns.extend(to);
+            }
+        }
+
+        let mut clauses = Self::table_definition(conn, table_name)?.map(|d| d.reference_clauses()).unwrap_or_default();
+        for key in &mut keys {
+            if let Some(i) = clauses.iter().position(|clause| clause.describes(key)) {
+                let clause = clauses.remove(i);
+                key.deferrable = clause.deferrable;
+                key.initially_deferred = clause.initially_deferred;
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Work out which columns a data diff reads from both sides and which of them form the key
+    fn keyed_table(&self, conn1: &Connection, conn2: &Connection, table_name: &str, key: &RowKey, databases: (&str, &str)) -> anyhow::Result<KeyedTable> {
+        let table_name = &Self::resolve_table_name(conn1, table_name)?;
+        Self::resolve_table_name(conn2, table_name)?;
+
+        let columns1 = self.get_table_columns(conn1, table_name)?;
+        let columns2 = self.get_table_columns(conn2, table_name)?;
+        // Generated columns follow from the others and a sync could not write them anyway
+        let names2: std::collections::HashSet<&str> = columns2.iter()
+            .filter(|c| c.generated.is_none())
+            .map(|c| c.name.as_str())
+            .collect();
+        let mut columns: Vec<String> = columns1.iter()
+            .filter(|c| c.generated.is_none() && names2.contains(c.name.as_str()))
+            .map(|c| c.name.clone())
+            .collect();
+
+        let key_columns = Self::row_key_columns(conn1, table_name, key)?;
+        for key in &key_columns {
+            if columns.contains(key) {
+                continue;
+            }
+            // The rowid is not a declared column, so it is read alongside the compared ones
+            if key == ROWID && Self::has_rowid(conn1, table_name) && Self::has_rowid(conn2, table_name) {
+                columns.insert(0, key.clone());
+                continue;
+            }
+            let database = if columns1.iter().any(|c| &c.name == key) { databases.1 } else { databases.0 };
+            return Err(AppError::InvalidInput(format!("Key column '{}' is missing from table '{}' in {}", key, table_name, database)).into());
+        }
+        let key_indexes: Vec<usize> = key_columns.iter()
+            .filter_map(|key| columns.iter().position(|c| c == key))
+            .collect();
+
+        // Both sides run the same queries, so both must agree on BINARY order
+        let collate_keys = Self::key_needs_collate(conn1, table_name, &key_columns)?
+            || Self::key_needs_collate(conn2, table_name, &key_columns)?;
+        Ok(KeyedTable {
+            name: table_name.to_string(),
+            columns,
+            key_indexes,
+            collate_keys,
+        })
+    }
+
+    fn has_rowid(conn: &Connection, table_name: &str) -> bool {
+        // WITHOUT ROWID tables and views reject the query
+        conn.prepare(&format!("SELECT {} FROM {} LIMIT 0", ROWID, Self::quote_identifier(table_name))).is_ok()
+    }
+
+    /// Resolve a `RowKey` to the columns that identify a row, in key order
+    fn row_key_columns(conn: &Connection, table_name: &str, key: &RowKey) -> anyhow::Result<Vec<String>> {
+        match key {
+            RowKey::PrimaryKey => {
+                let primary_key = Self::primary_key_columns(conn, table_name)?;
+                if !primary_key.is_empty() {
+                    Ok(primary_key)
+                } else if Self::has_rowid(conn, table_name) {
+                    Ok(vec![ROWID.to_string()])
+                } else {
+                    Err(AppError::InvalidInput(format!("'{}' has no primary key or rowid; choose the key columns to match rows by", table_name)).into())
+                }
+            }
+            RowKey::Rowid => {
+                if !Self::has_rowid(conn, table_name) {
+                    return Err(AppError::InvalidInput(format!("'{}' has no rowid", table_name)).into());
+                }
+                Ok(vec![ROWID.to_string()])
+            }
+            RowKey::UniqueIndex { name } => {
+                let flags: Option<(bool, bool)> = conn.query_row(
+                    "SELECT \"unique\", \"partial\" FROM pragma_index_list(?1) WHERE name = ?2",
+                    [table_name, name.as_str()],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                ).optional()?;
+                match flags {
+                    None => return Err(AppError::InvalidInput(format!("Index '{}' does not belong to table '{}'", name, table_name)).into()),
+                    Some((false, _)) => return Err(AppError::InvalidInput(format!("Index '{}' is not unique", name)).into()),
+                    Some((_, true)) => return Err(AppError::InvalidInput(format!("Index '{}' is partial and does not cover every row", name)).into()),
+                    Some(_) => {}
+                }
+
+                let mut stmt = conn.prepare("SELECT name FROM pragma_index_xinfo(?1) WHERE key = 1 ORDER BY seqno")?;
+                let columns: Vec<Option<String>> = stmt.query_map([name], |row| row.get(0))?
+                    .collect::<RusqliteResult<Vec<_>>>()?;
+                columns.into_iter()
+                    .map(|column| column.ok_or_else(|| {
+                        AppError::InvalidInput(format!("Index '{}' is on an expression and cannot be used as a row key", name)).into()
+                    }))
+                    .collect()
+            }
+            RowKey::Columns { columns } => {
+                if columns.is_empty() {
+                    return Err(AppError::InvalidInput("No key columns given".to_string()).into());
+                }
+                Ok(columns.clone())
+            }
+        }
+    }
+
+    /// Whether key comparisons need an explicit `COLLATE BINARY` to match `compare_values`.
+    /// Not needed for a rowid alias or when a BINARY index covers exactly the key columns,
+    /// and worth avoiding there: an explicit COLLATE stops SQLite using the index for ranges.
+    fn key_needs_collate(conn: &Connection, table_name: &str, key_columns: &[String]) -> anyhow::Result<bool> {
+        if let [key] = key_columns {
+            let (declared, rowid_alias): (bool, bool) = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2),
+                        EXISTS(SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2 AND pk = 1 AND upper(type) = 'INTEGER')
+                        AND (SELECT COUNT(*) FROM pragma_table_info(?1) WHERE pk > 0) = 1",
+                [table_name, key.as_str()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            if rowid_alias || (!declared && key == ROWID) {
+                return Ok(false);
+            }
+        }
+
+        let mut stmt = conn.prepare("SELECT name FROM pragma_index_list(?1) WHERE \"partial\" = 0")?;
+        let indexes: Vec<String> = stmt.query_map([table_name], |row| row.get(0))?
+            .collect::<RusqliteResult<Vec<_>>>()?;
+        for index in indexes {
+            let mut stmt = conn.prepare("SELECT name, coll FROM pragma_index_xinfo(?1) WHERE key = 1 ORDER BY seqno")?;
+            let index_columns: Vec<(Option<String>, String)> = stmt.query_map([&index], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<RusqliteResult<Vec<_>>>()?;
+            let covers_key = index_columns.len() == key_columns.len()
+                && index_columns.iter().zip(key_columns).all(|((name, coll), key)| {
+                    name.as_deref() == Some(key.as_str()) && coll.eq_ignore_ascii_case("BINARY")
+                });
+            if covers_key {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Diff the rows of `table_name` in two connections by primary key. Both sides are read
+    /// in key order and merged, so memory use does not grow with the table size. With
+    /// `chunk_size` set, key ranges are compared by digest first and only ranges whose
+    /// digests differ are diffed row by row. At most `max_rows` rows are kept per list;
+    /// the summary counts are always complete.
+    pub fn compare_table_data(&self, db1: &ConnectionId, db2: &ConnectionId, table_name: &str, options: &DataCompareOptions, cancel: &CancelToken) -> anyhow::Result<DataComparison> {
+        let database1 = self.connection_info(db1)?.path;
+        let database2 = self.connection_info(db2)?.path;
+
+        self.with_connections(db1, db2, cancel, |conn1, conn2| {
+            let table = self.keyed_table(conn1, conn2, table_name, options.key.as_ref().unwrap_or(&RowKey::PrimaryKey), (&database1, &database2))?;
+
+            let total = Self::count_rows(conn1, &table.name)? + Self::count_rows(conn2, &table.name)?;
+
+            let mut diff = RowDiffCollector::new(&table, options.max_rows, DiffProgress::new(&table.name, total));
+            match options.chunk_size {
+                Some(0) => {
+                    return Err(AppError::InvalidInput("chunk_size must be greater than 0".to_string()).into());
+                }
+                Some(chunk_size) => {
+                    // Row-value comparisons never match NULL keys, so those rows are diffed on their own
+                    Self::merge_rows(conn1, conn2, &table, Some(&KeyRange::NullKeys), &mut diff, cancel)?;
+                    // The first and last ranges are open-ended so keys only present in database2 are covered
+                    let mut lower = None;
+                    for boundary in Self::chunk_boundaries(conn1, &table, chunk_size, cancel)? {
+                        Self::diff_range(conn1, conn2, &table, lower.take(), Some(boundary.clone()), &mut diff, cancel)?;
+                        lower = Some(boundary);
+                    }
+                    Self::diff_range(conn1, conn2, &table, lower, None, &mut diff, cancel)?;
+                }
+                None => Self::merge_rows(conn1, conn2, &table, None, &mut diff, cancel)?,
+            }
+            diff.progress().report_done(cancel);
+
+            Ok(diff.finish(database1, database2))
+        })
+    }
+
+    fn count_rows(conn: &Connection, table_name: &str) -> anyhow::Result<u64> {
+        let count: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM {}", Self::quote_identifier(table_name)), [], |row| row.get(0))?;
+        Ok(count as u64)
+    }
+
+    /// (source, target) of a sync between `db1` and `db2`
+    fn sync_endpoints<'a>(db1: &'a ConnectionId, db2: &'a ConnectionId, direction: SyncDirection) -> (&'a ConnectionId, &'a ConnectionId) {
+        match direction {
+            SyncDirection::FirstToSecond => (db1, db2),
+            SyncDirection::SecondToFirst => (db2, db1),
+        }
+    }
+
+    /// Work out, table by table, the statements that make the target's rows match the source's
+    fn plan_sync(&self, source: &Connection, target: &Connection, tables: &[String], options: &SyncOptions, databases: (&str, &str), cancel: &CancelToken) -> anyhow::Result<Vec<TableSync>> {
+        if tables.is_empty() {
+            return Err(AppError::InvalidInput("No tables selected".to_string()).into());
+        }
+
+        let mut plans = Vec::with_capacity(tables.len());
+        for table_name in tables {
+            cancel.check()?;
+            let key = options.keys.get(table_name).unwrap_or(&RowKey::PrimaryKey);
+            let table = self.keyed_table(source, target, table_name, key, databases)?;
+            let total = Self::count_rows(source, &table.name)? + Self::count_rows(target, &table.name)?;
+
+            let mut planner = SyncPlanner::new(&table, options, DiffProgress::new(&table.name, total));
+            Self::merge_rows(source, target, &table, None, &mut planner, cancel)?;
+            planner.progress().report_done(cancel);
+            plans.push(planner.sync);
+        }
+        Ok(plans)
+    }
+
+    /// Produce a transactional SQL script that transforms the target's data into the
+    /// source's for the selected tables. Nothing is written to either database.
+    pub fn generate_sync_script(&self, db1: &ConnectionId, db2: &ConnectionId, tables: &[String], options: &SyncOptions, cancel: &CancelToken) -> anyhow::Result<SyncScript> {
+        let (source_id, target_id) = Self::sync_endpoints(db1, db2, options.direction);
+        let source = self.connection_info(source_id)?.path;
+        let target = self.connection_info(target_id)?.path;
+
+        let plans = self.with_connections(source_id, target_id, cancel, |source_conn, target_conn| {
+            self.plan_sync(source_conn, target_conn, tables, options, (&source, &target), cancel)
+        })?;
+
+        let mut sql = format!("-- Data sync script\n-- Source: {}\n-- Target: {}\n\nBEGIN TRANSACTION;\n", source, target);
+        // Rows of related tables may be fixed up in any order; foreign keys are checked at COMMIT
+        sql.push_str("PRAGMA defer_foreign_keys = ON;\n");
+        for plan in &plans {
+            let summary = plan.summary();
+            sql.push_str(&format!(
+                "\n-- {}: {} deletes, {} updates, {} inserts\n",
+                plan.table_name, summary.deletes, summary.updates, summary.inserts
+            ));
+            for statement in plan.statements() {
+                sql.push_str(statement);
+                sql.push_str(";\n");
+            }
+        }
+        sql.push_str("\nCOMMIT;\n");
+
+        Ok(SyncScript {
+            source,
+            target,
+            sql,
+            tables: plans.iter().map(TableSync::summary).collect(),
+        })
+    }
+
+    /// Apply the sync to the target in a single transaction. Statements rejected by a
+    /// constraint, and foreign keys the sync leaves dangling, are reported as conflicts and
+    /// roll everything back; so does any other error. A dry run always rolls back, leaving
+    /// only the affected row counts.
+    pub fn apply_sync(&self, db1: &ConnectionId, db2: &ConnectionId, tables: &[String], options: &SyncOptions, dry_run: bool, cancel: &CancelToken) -> anyhow::Result<SyncResult> {
+        let (source_id, target_id) = Self::sync_endpoints(db1, db2, options.direction);
+        let source = self.connection_info(source_id)?.path;
+        let target_info = self.connection_info(target_id)?;
+        if target_info.open_mode != OpenMode::ReadWrite {
+            return Err(AppError::InvalidInput(format!(
+                "{} is not open for writing; reconnect it in read-write mode to apply a sync",
+                target_info.path
+            )).into());
+        }
+        let target = target_info.path;
+
+        self.with_connections(source_id, target_id, cancel, |source_conn, target_conn| {
+            let plans = self.plan_sync(source_conn, target_conn, tables, options, (&source, &target), cancel)?;
+
+            // Dropping the transaction on an early return rolls it back
+            let tx = target_conn.unchecked_transaction()?;
+            tx.execute_batch("PRAGMA defer_foreign_keys = ON")?;
+            let existing_violations = Self::foreign_key_violations(&tx)?;
+
+            let total = plans.iter().map(|plan| plan.statements().count() as u64).sum();
+            let mut done = 0u64;
+            cancel.report(ProgressPhase::ApplyingChanges, Some(target_id), None, done, total);
+
+            let mut applied = Vec::with_capacity(plans.len());
+            let mut conflicts = Vec::new();
+            for plan in &plans {
+                let mut counts = TableSyncSummary { table_name: plan.table_name.clone(), ..Default::default() };
+                for (statements, count) in [
+                    (&plan.deletes, &mut counts.deletes),
+                    (&plan.updates, &mut counts.updates),
+                    (&plan.inserts, &mut counts.inserts),
+                ] {
+                    for statement in statements {
+                        match tx.execute(statement, []) {
+                            Ok(rows) => *count += rows as u64,
+                            // SQLite undoes just the failing statement, so keep going to report every conflict
+                            Err(e) if e.sqlite_error_code() == Some(rusqlite::ErrorCode::ConstraintViolation) => {
+                                conflicts.push(SyncConflict {
+                                    table_name: plan.table_name.clone(),
+                                    statement: Some(statement.clone()),
+                                    message: e.to_string(),
+                                });
+                            }
+                            Err(e) => return Err(e.into()),
+                        }
+                        done += 1;
+                        if done.is_multiple_of(ROW_PROGRESS_INTERVAL) {
+                            cancel.check()?;
+                            cancel.report(ProgressPhase::ApplyingChanges, Some(target_id), Some(&plan.table_name), done, total);
+                        }
+                    }
+                }
+                applied.push(counts);
+            }
+            cancel.report(ProgressPhase::ApplyingChanges, Some(target_id), None, done, total);
+
+            // Foreign keys are deferred (and may not be enforced at all on this connection),
+            // so look for violations the sync introduced rather than waiting for COMMIT
+            for violation in Self::foreign_key_violations(&tx)? {
+                if !existing_violations.contains(&violation) {
+                    conflicts.push(violation.into_conflict());
+                }
+            }
+
+            let committed = !dry_run && conflicts.is_empty();
+            if committed {
+                tx.commit()?;
+            } else {
+                tx.rollback()?;
+            }
+
+            Ok(SyncResult {
+                source: source.clone(),
+                target: target.clone(),
+                dry_run,
+                committed,
+                tables: applied,
+                conflicts,
+            })
+        })
+    }
+
+    fn foreign_key_violations(conn: &Connection) -> anyhow::Result<Vec<ForeignKeyViolation>> {
+        let mut stmt = conn.prepare(r#"SELECT "table", rowid, parent, fkid FROM pragma_foreign_key_check"#)?;
+        let violations = stmt.query_map([], |row| {
+            Ok(ForeignKeyViolation {
+                table_name: row.get(0)?,
+                rowid: row.get(1)?,
+                parent: row.get(2)?,
+                foreign_key_id: row.get(3)?,
+            })
+        })?.collect::<RusqliteResult<Vec<_>>>()?;
+        Ok(violations)
+    }
+
+    /// Generate a script that gives `db1` the schema of `db2`, following `compare_schemas`.
+    /// Nothing is written to either database. The script turns foreign key enforcement off
+    /// while it runs and on at the end.
+    /// `renames` are column renames the user confirmed, usually picked from the
+    /// `renamed_columns` proposals of the comparison; each must pair a removed with an added column
+    pub fn generate_migration_script(&self, db1: &ConnectionId, db2: &ConnectionId, renames: &[ColumnRename], cancel: &CancelToken) -> anyhow::Result<MigrationScript> {
+        // Declared types are compared exactly so the migrated schema spells them as database2 does
+        let comparison = self.compare_schemas(db1, db2, &SchemaCompareOptions::default(), cancel)?;
+        for rename in renames {
+            let valid = comparison.modified_tables.iter().any(|diff| {
+                diff.table_name == rename.table_name
+                    && diff.removed_columns.contains(&rename.old_name)
+                    && diff.added_columns.iter().any(|c| c.name == rename.new_name)
+            });
+            if !valid || renames.iter().filter(|r| r.table_name == rename.table_name && (r.old_name == rename.old_name || r.new_name == rename.new_name)).count() > 1 {
+                return Err(AppError::InvalidInput(format!(
+                    "Cannot rename {}.{} to {}: the rename must pair a removed column with an added one, each used once",
+                    rename.table_name, rename.old_name, rename.new_name
+                )).into());
+            }
+        }
+        self.with_connections(db1, db2, cancel, |conn1, conn2| self.migration_script(&comparison, renames, conn1, conn2))
+    }
+
+    /// Turn a schema comparison into statements run against database1. Tables are altered in
+    /// place where `ALTER TABLE` allows it and otherwise rebuilt with SQLite's 12-step procedure.
+    /// Removed and modified indexes, triggers and views are dropped before the tables change;
+    /// added and modified ones are created once the tables are in their final shape.
+    fn migration_script(&self, comparison: &SchemaComparison, renames: &[ColumnRename], conn1: &Connection, conn2: &Connection) -> anyhow::Result<MigrationScript> {
+        let schema1 = Schema::read(conn1)?;
+        let schema2 = Schema::read(conn2)?;
+        let objects = [
+            (SchemaObjectKind::View, &comparison.added_views, &comparison.removed_views, &comparison.modified_views),
+            (SchemaObjectKind::Trigger, &comparison.added_triggers, &comparison.removed_triggers, &comparison.modified_triggers),
+            (SchemaObjectKind::Index, &comparison.added_indexes, &comparison.removed_indexes, &comparison.modified_indexes),
+        ];
+        let dropped: std::collections::HashSet<String> = objects.iter()
+            .flat_map(|(_, _, removed, modified)| removed.iter().chain(modified.iter().map(|m| &m.name)))
+            .map(|name| name.to_lowercase())
+            .collect();
+
+        let context = MigrationContext { conn1, conn2, schema1: &schema1, schema2: &schema2, dropped: &dropped, renames };
+        let mut plan = MigrationPlan::default();
+
+        let mut removed: Vec<&String> = comparison.removed_tables.iter().collect();
+        removed.sort();
+        for table_name in removed {
+            plan.push(table_name, MigrationAction::Drop, Vec::new(), vec![format!("DROP TABLE {}", Self::quote_identifier(table_name))]);
+        }
+
+        let mut modified: Vec<&TableDiff> = comparison.modified_tables.iter().collect();
+        modified.sort_by(|a, b| a.table_name.cmp(&b.table_name));
+        for diff in modified {
+            self.migrate_table(diff, &context, &mut plan)?;
+        }
+
+        let mut added: Vec<&String> = comparison.added_tables.iter().collect();
+        added.sort();
+        for table_name in added {
+            match schema2.table_sql(table_name) {
+                Some(sql) => {
+                    let mut statements = vec![sql.to_string()];
+                    statements.extend(schema2.dependents(table_name).map(str::to_string));
+                    plan.push(table_name, MigrationAction::Create, Vec::new(), statements);
+                }
+                None => plan.warnings.push(format!("{} has no stored CREATE statement; create it by hand", table_name)),
+            }
+        }
+
+        // Dropping, creating or rebuilding a table already takes care of its indexes and triggers
+        let recreated: std::collections::HashSet<String> = plan.tables.iter()
+            .filter(|t| t.action != MigrationAction::Alter)
+            .map(|t| t.table_name.to_lowercase())
+            .collect();
+        let handled_by_table = |schema: &Schema, kind: SchemaObjectKind, name: &str| {
+            schema.object(kind, name).is_some_and(|o| kind != SchemaObjectKind::View && recreated.contains(&o.table_name.to_lowercase()))
+        };
+
+        let tables = std::mem::take(&mut plan);
+        for (kind, _, removed, modified) in &objects {
+            for (name, replaced) in removed.iter().map(|n| (n, false)).chain(modified.iter().map(|m| (&m.name, true))) {
+                if !handled_by_table(&schema1, *kind, name) {
+                    plan.drop_object(*kind, name, replaced);
+                }
+            }
+        }
+        plan.append(tables);
+        for (kind, added, _, modified) in objects.iter().rev() {
+            for (name, replaced) in added.iter().map(|n| (n, false)).chain(modified.iter().map(|m| (&m.name, true))) {
+                if handled_by_table(&schema2, *kind, name) {
+                    continue;
+                }
+                match schema2.object(*kind, name).and_then(|o| o.sql.as_deref()) {
+                    Some(sql) => plan.create_object(*kind, name, sql, replaced),
+                    None => plan.warnings.push(format!("{} {} has no stored CREATE statement; create it by hand", object_type(*kind), name)),
+                }
+            }
+        }
+
+        Ok(MigrationScript {
+            sql: plan.script(&comparison.database2, &comparison.database1),
+            source: comparison.database2.clone(),
+            target: comparison.database1.clone(),
+            tables: plan.tables,
+            objects: plan.objects,
+            warnings: plan.warnings,
+        })
+    }
+
+    fn migrate_table(&self, diff: &TableDiff, context: &MigrationContext, plan: &mut MigrationPlan) -> anyhow::Result<()> {
+        let MigrationContext { conn1, conn2, schema1, schema2, dropped, .. } = *context;
+        let table_name = &diff.table_name;
+        let renames: Vec<&ColumnRename> = context.renames.iter().filter(|r| &r.table_name == table_name).collect();
+        let definitions = schema1.table_sql(table_name).and_then(TableDefinition::parse)
+            .zip(schema2.table_sql(table_name).and_then(TableDefinition::parse));
+        let Some((old_definition, new_definition)) = definitions else {
+            plan.warnings.push(format!("{} is a virtual table or its CREATE statement could not be read; migrate it by hand", table_name));
+            return Ok(());
+        };
+        let old_columns = self.get_table_columns(conn1, table_name)?;
+        let new_columns = self.get_table_columns(conn2, table_name)?;
+        // The name a column of database1 has in database2
+        let renamed = |old: &str| renames.iter().find(|r| r.old_name == old).map_or(old, |r| r.new_name.as_str()).to_string();
+        let old_column = |new: &ColumnInfo| old_columns.iter().find(|old| renamed(&old.name) == new.name);
+
+        // Anything in `rebuild_reasons` forces the rebuild; otherwise the alterations are enough
+        let mut rebuild_reasons = Vec::new();
+        for column in &diff.modified_columns {
+            for change in &column.changes {
+                rebuild_reasons.push(format!("{}: {}", column.column_name, change));
+            }
+        }
+        rebuild_reasons.extend(diff.changes.iter().cloned());
+
+        let mut alterations = Vec::new();
+        let mut changes = Vec::new();
+        // Renames go first, so an added column may take a name a renamed one gave up
+        for rename in &renames {
+            let old = old_columns.iter().find(|c| c.name == rename.old_name);
+            let new = new_columns.iter().find(|c| c.name == rename.new_name);
+            if let Some((old, new)) = old.zip(new) {
+                for change in diff::column_changes(old, new, TypeComparison::Declared) {
+                    rebuild_reasons.push(format!("{}: {}", new.name, change));
+                }
+            }
+            alterations.push(format!(
+                "ALTER TABLE {} RENAME COLUMN {} TO {}",
+                Self::quote_identifier(table_name), Self::quote_identifier(&rename.old_name), Self::quote_identifier(&rename.new_name)
+            ));
+            changes.push(format!("rename column {} to {}", rename.old_name, rename.new_name));
+        }
+        let is_renamed = |name: &String| renames.iter().any(|r| &r.old_name == name || &r.new_name == name);
+        for column in old_columns.iter().filter(|c| diff.removed_columns.contains(&c.name) && !is_renamed(&c.name)) {
+            match Self::drop_column_blocker(table_name, &column.name, &old_definition, schema1, dropped) {
+                Some(blocker) => rebuild_reasons.push(format!("{}: cannot be dropped in place ({})", column.name, blocker)),
+                None => {
+                    alterations.push(format!("ALTER TABLE {} DROP COLUMN {}", Self::quote_identifier(table_name), Self::quote_identifier(&column.name)));
+                    changes.push(format!("drop column {}", column.name));
+                }
+            }
+        }
+        let added: Vec<&ColumnInfo> = new_columns.iter()
+            .filter(|c| diff.added_columns.iter().any(|a| a.name == c.name) && !is_renamed(&c.name))
+            .collect();
+        for column in &added {
+            let definition = new_definition.column(&column.name);
+            match Self::add_column_blocker(column, definition) {
+                Some(blocker) => rebuild_reasons.push(format!("{}: cannot be added in place ({})", column.name, blocker)),
+                None => {
+                    alterations.push(format!("ALTER TABLE {} ADD COLUMN {}", Self::quote_identifier(table_name), definition.unwrap_or_default()));
+                    changes.push(format!("add column {}", column.name));
+                }
+            }
+        }
+
+        // SQLite has no way to change the foreign keys of a table, except that ADD COLUMN can
+        // bring a column-level REFERENCES clause with it and RENAME COLUMN updates the keys
+        let follows_rename = |key: &ForeignKeyInfo| {
+            let renamed_key = ForeignKeyInfo { columns: key.columns.iter().map(|c| renamed(c)).collect(), ..key.clone() };
+            &renamed_key != key && diff.added_foreign_keys.contains(&renamed_key)
+        };
+        let mut renamed_keys = Vec::new();
+        for key in &diff.removed_foreign_keys {
+            if follows_rename(key) {
+                renamed_keys.push(key.columns.iter().map(|c| renamed(c)).collect::<Vec<_>>());
+            } else {
+                rebuild_reasons.push(format!("foreign key ({}) removed", key.columns.join(", ")));
+            }
+        }
+        for key in &diff.modified_foreign_keys {
+            rebuild_reasons.push(format!("foreign key ({}): {}", key.columns.join(", "), key.changes.join(", ")));
+        }
+        for key in diff.added_foreign_keys.iter().filter(|k| !renamed_keys.contains(&k.columns)) {
+            let on_new_column = match key.columns.as_slice() {
+                [column] => added.iter().any(|c| &c.name == column)
+                    && new_definition.column(column).is_some_and(|d| has_keywords(d, &["references"])),
+                _ => false,
+            };
+            if !on_new_column {
+                rebuild_reasons.push(format!("foreign key ({}) added", key.columns.join(", ")));
+            }
+        }
+
+        // ADD COLUMN appends, so altering in place only works when the new columns come last
+        let altered_order: Vec<String> = old_columns.iter()
+            .filter(|c| !diff.removed_columns.contains(&c.name) || is_renamed(&c.name))
+            .map(|c| renamed(&c.name))
+            .chain(added.iter().map(|c| c.name.clone()))
+            .collect();
+        if altered_order != new_columns.iter().map(|c| c.name.clone()).collect::<Vec<_>>() {
+            let moved: Vec<&str> = diff.moved_columns.iter().map(|m| m.column_name.as_str()).collect();
+            rebuild_reasons.push(match moved.as_slice() {
+                [] => "column order differs".to_string(),
+                moved => format!("column order differs ({} moved)", moved.join(", ")),
+            });
+        }
+
+        if rebuild_reasons.is_empty() {
+            if !alterations.is_empty() {
+                plan.push(table_name, MigrationAction::Alter, changes, alterations);
+            }
+            return Ok(());
+        }
+
+        for new in new_columns.iter().filter(|c| c.generated.is_none()) {
+            let old = old_column(new);
+            let has_default = new.default_value.as_deref().is_some_and(|d| !d.eq_ignore_ascii_case("null"));
+            if !new.is_nullable && !has_default && old.is_none_or(|old| old.is_nullable) {
+                plan.warnings.push(format!("{}.{} becomes NOT NULL; copying existing rows fails if any would be NULL", table_name, new.name));
+            }
+        }
+
+        let new_table = Self::quote_identifier(&format!("{}_migration_new", table_name));
+        let table = Self::quote_identifier(table_name);
+        let mut statements = vec![format!("CREATE TABLE {} {}", new_table, new_definition.body)];
+        // Generated columns compute their own values
+        let copied: Vec<(String, String)> = new_columns.iter()
+            .filter(|c| c.generated.is_none())
+            .filter_map(|c| old_column(c).map(|old| (Self::quote_identifier(&c.name), Self::quote_identifier(&old.name))))
+            .collect();
+        if !copied.is_empty() {
+            let (targets, sources): (Vec<String>, Vec<String>) = copied.into_iter().unzip();
+            statements.push(format!("INSERT INTO {} ({}) SELECT {} FROM {}", new_table, targets.join(", "), sources.join(", "), table));
+        }
+        statements.push(format!("DROP TABLE {}", table));
+        statements.push(format!("ALTER TABLE {} RENAME TO {}", new_table, table));
+        // Dropping the old table took its indexes and triggers with it
+        statements.extend(schema2.dependents(table_name).map(str::to_string));
+        plan.push(table_name, MigrationAction::Rebuild, rebuild_reasons, statements);
+        Ok(())
+    }
+
+    /// Why `ALTER TABLE ADD COLUMN` cannot add this column, if it can't
+    fn add_column_blocker(column: &ColumnInfo, definition: Option<&str>) -> Option<&'static str> {
+        let Some(definition) = definition else {
+            return Some("column definition not found");
+        };
+        if column.is_primary_key || has_keywords(definition, &["primary", "key"]) {
+            return Some("PRIMARY KEY");
+        }
+        if has_keywords(definition, &["unique"]) {
+            return Some("UNIQUE");
+        }
+        if column.generated.as_ref().is_some_and(|g| g.stored) {
+            return Some("STORED generated column");
+        }
+        if column.generated.is_some() {
+            return None;
+        }
+        let default = column.default_value.as_deref().map(|d| d.trim().to_ascii_lowercase());
+        if let Some(default) = &default {
+            if default.starts_with('(') || matches!(default.as_str(), "current_time" | "current_date" | "current_timestamp") {
+                return Some("non-constant default");
+            }
+        }
+        if !column.is_nullable && default.as_deref().is_none_or(|d| d == "null") {
+            return Some("NOT NULL without a default");
+        }
+        None
+    }
+
+    /// Why `ALTER TABLE DROP COLUMN` cannot drop this column, if it can't. Any mention of the
+    /// column in a constraint, or in an index, trigger or view that is not `dropped` first,
+    /// counts, which errs towards rebuilding.
+    fn drop_column_blocker(table_name: &str, column: &str, definition: &TableDefinition, schema: &Schema, dropped: &std::collections::HashSet<String>) -> Option<String> {
+        let Some(column_definition) = definition.column(column) else {
+            return Some("column definition not found".to_string());
+        };
+        if has_keywords(column_definition, &["primary", "key"]) {
+            return Some("PRIMARY KEY".to_string());
+        }
+        if has_keywords(column_definition, &["unique"]) {
+            return Some("UNIQUE".to_string());
+        }
+        if definition.items.iter().any(|item| item != column_definition && mentions(item, column)) {
+            return Some("used by a constraint or generated column".to_string());
+        }
+        for object in schema.objects.iter().filter(|o| !dropped.contains(&o.name.to_lowercase())) {
+            let Some(sql) = &object.sql else { continue };
+            let on_table = object.table_name.eq_ignore_ascii_case(table_name);
+            let related = match object.kind.as_str() {
+                "index" | "trigger" => on_table,
+                _ => !on_table && mentions(sql, table_name),
+            };
+            if related && mentions(sql, column) {
+                return Some(format!("used by {} {}", object.kind, object.name));
+            }
+        }
+        None
+    }
+
+    fn quote_identifier(name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+
+    /// A value as an SQL literal that reads back as the same value and storage class
+    fn sql_literal(value: &Value) -> String {
+        match value {
+            Value::Null => "NULL".to_string(),
+            Value::Integer(i) => i.to_string(),
+            Value::Real(f) if f.is_nan() => "NULL".to_string(),
+            Value::Real(f) if f.is_infinite() => if *f > 0.0 { "9e999".to_string() } else { "-9e999".to_string() },
+            // Debug formatting is the shortest text that round-trips and always keeps a `.` or exponent
+            Value::Real(f) => format!("{:?}", f),
+            Value::Text(s) => format!("'{}'", s.replace('\'', "''")),
+            Value::Blob(b) => format!("X'{}'", b.iter().map(|byte| format!("{:02X}", byte)).collect::<String>()),
+        }
+    }
+
+    /// Merge both sides of `range` (the whole table when `None`) in key order into `diff`
+    fn merge_rows(conn1: &Connection, conn2: &Connection, table: &KeyedTable, range: Option<&KeyRange>, diff: &mut impl RowDiffSink, cancel: &CancelToken) -> anyhow::Result<()> {
+        let (select, params) = table.select(&table.columns, range);
+        let mut stmt1 = conn1.prepare(&select)?;
+        let mut stmt2 = conn2.prepare(&select)?;
+        let mut rows1 = stmt1.query(rusqlite::params_from_iter(&params))?;
+        let mut rows2 = stmt2.query(rusqlite::params_from_iter(&params))?;
+
+        let column_count = table.columns.len();
+        let mut left = Self::next_values(&mut rows1, column_count)?;
+        let mut right = Self::next_values(&mut rows2, column_count)?;
+
+        loop {
+            let ordering = match (&left, &right) {
+                (None, None) => break,
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (Some(l), Some(r)) => Self::compare_keys(l, r, &table.key_indexes),
+            };
+
+            match ordering {
+                std::cmp::Ordering::Less => {
+                    let old = left.take().unwrap_or_default();
+                    left = Self::next_unique(&mut rows1, &old, table)?;
+                    diff.removed(old);
+                    diff.progress().advance(1);
+                }
+                std::cmp::Ordering::Greater => {
+                    let new = right.take().unwrap_or_default();
+                    right = Self::next_unique(&mut rows2, &new, table)?;
+                    diff.added(new);
+                    diff.progress().advance(1);
+                }
+                std::cmp::Ordering::Equal => {
+                    let (old, new) = (left.take().unwrap_or_default(), right.take().unwrap_or_default());
+                    left = Self::next_unique(&mut rows1, &old, table)?;
+                    right = Self::next_unique(&mut rows2, &new, table)?;
+                    diff.matched(old, new);
+                    diff.progress().advance(2);
+                }
+            }
+            diff.progress().checkpoint(cancel)?;
+        }
+
+        Ok(())
+    }
+
+    /// Keys that split the non-NULL keys of database1 into ranges of `chunk_size` rows
+    fn chunk_boundaries(conn: &Connection, table: &KeyedTable, chunk_size: u64, cancel: &CancelToken) -> anyhow::Result<Vec<Vec<Value>>> {
+        let keys = table.key_columns();
+        let (select, params) = table.select(&keys, Some(&KeyRange::all()));
+        let mut stmt = conn.prepare(&select)?;
+        let mut rows = stmt.query(rusqlite::params_from_iter(&params))?;
+
+        let mut boundaries = Vec::new();
+        let mut index = 0u64;
+        while let Some(key) = Self::next_values(&mut rows, keys.len())? {
+            if index > 0 && index.is_multiple_of(chunk_size) {
+                cancel.check()?;
+                boundaries.push(key);
+            }
+            index += 1;
+        }
+
+        Ok(boundaries)
+    }
+
+    /// Compare the digests of the key range `[lower, upper)` and descend into it only if they
+    /// differ: ranges are halved at database1's median key until they are small enough to merge
+    fn diff_range(
+        conn1: &Connection,
+        conn2: &Connection,
+        table: &KeyedTable,
+        lower: Option<Vec<Value>>,
+        upper: Option<Vec<Value>>,
+        diff: &mut impl RowDiffSink,
+        cancel: &CancelToken,
+    ) -> anyhow::Result<()> {
+        cancel.check()?;
+        let range = KeyRange::Between { lower: lower.clone(), upper: upper.clone() };
+        let digest1 = Self::range_digest(conn1, table, &range)?;
+        let digest2 = Self::range_digest(conn2, table, &range)?;
+
+        if digest1 == digest2 {
+            diff.identical(digest1.0);
+            diff.progress().advance(digest1.0 * 2);
+            return diff.progress().checkpoint(cancel);
+        }
+        if digest1.0 <= LEAF_RANGE_ROWS {
+            return Self::merge_rows(conn1, conn2, table, Some(&range), diff, cancel);
+        }
+
+        let keys = table.key_columns();
+        let (select, mut params) = table.select(&keys, Some(&range));
+        params.push(Value::Integer((digest1.0 / 2) as i64));
+        let median = conn1.query_row(&format!("{} LIMIT 1 OFFSET ?", select), rusqlite::params_from_iter(&params), |row| {
+            (0..keys.len()).map(|i| row.get(i)).collect::<RusqliteResult<Vec<Value>>>()
+        })?;
+        // Only a repeated key can put the median at `lower`, and splitting there would not
+        // shrink the range; the merge reports the repeated key instead
+        let at_lower = lower.as_ref().is_some_and(|lower| lower.iter().zip(&median).all(|(l, m)| Self::compare_values(l, m).is_eq()));
+        if at_lower {
+            return Self::merge_rows(conn1, conn2, table, Some(&range), diff, cancel);
+        }
+
+        Self::diff_range(conn1, conn2, table, lower, Some(median.clone()), diff, cancel)?;
+        Self::diff_range(conn1, conn2, table, Some(median), upper, diff, cancel)
+    }
+
+    /// Row count and digest of the compared values in `range`. Both are aggregated by SQLite,
+    /// so no row of the range is copied out of the database.
+    fn range_digest(conn: &Connection, table: &KeyedTable, range: &KeyRange) -> anyhow::Result<(u64, u64)> {
+        let (filter, params) = table.filter(Some(range));
+        let query = format!(
+            "SELECT COUNT(*), {}({}) FROM {}{}",
+            ROW_DIGEST_FUNCTION,
+            table.columns.iter().map(|c| Self::quote_identifier(c)).collect::<Vec<_>>().join(", "),
+            Self::quote_identifier(&table.name),
+            filter,
+        );
+        let (count, digest): (i64, i64) = conn.query_row(&query, rusqlite::params_from_iter(&params), |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok((count as u64, digest as u64))
+    }
+
+    /// Fetch the row after `previous` on one side of a merge, rejecting a repeated key:
+    /// rows can only be matched if the key identifies them. Keys containing NULL are exempt,
+    /// as unique indexes allow any number of them.
+    fn next_unique(rows: &mut rusqlite::Rows<'_>, previous: &[Value], table: &KeyedTable) -> anyhow::Result<Option<Vec<Value>>> {
+        let next = Self::next_values(rows, table.columns.len())?;
+        if let Some(row) = &next {
+            let has_null = table.key_indexes.iter().any(|&i| matches!(row[i], Value::Null));
+            if !has_null && Self::compare_keys(previous, row, &table.key_indexes).is_eq() {
+                return Err(AppError::InvalidInput(format!(
+                    "Key ({}) does not identify rows uniquely in table '{}'",
+                    table.key_columns().join(", "),
+                    table.name
+                )).into());
+            }
+        }
+        Ok(next)
+    }
+
+    fn next_values(rows: &mut rusqlite::Rows<'_>, column_count: usize) -> anyhow::Result<Option<Vec<Value>>> {
+        match rows.next()? {
+            Some(row) => Ok(Some((0..column_count).map(|i| row.get(i)).collect::<RusqliteResult<Vec<Value>>>()?)),
+            None => Ok(None),
+        }
+    }
+
+    fn compare_keys(left: &[Value], right: &[Value], key_indexes: &[usize]) -> std::cmp::Ordering {
+        key_indexes.iter()
+            .map(|&i| Self::compare_values(&left[i], &right[i]))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    }
+
+    /// SQLite's ordering for values compared with BINARY collation: NULL, then numbers
+    /// (integers and reals compared by value), then text, then blobs
+    fn compare_values(left: &Value, right: &Value) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+
+        fn class(value: &Value) -> u8 {
+            match value {
+                Value::Null => 0,
+                Value::Integer(_) | Value::Real(_) => 1,
+                Value::Text(_) => 2,
+                Value::Blob(_) => 3,
+            }
+        }
+
+        match (left, right) {
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Integer(a), Value::Real(b)) => (*a as f64).partial_cmp(b).unwrap_or(Ordering::Equal),
+            (Value::Real(a), Value::Integer(b)) => a.partial_cmp(&(*b as f64)).unwrap_or(Ordering::Equal),
+            (Value::Real(a), Value::Real(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            (Value::Text(a), Value::Text(b)) => a.as_bytes().cmp(b.as_bytes()),
+            (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+            _ => class(left).cmp(&class(right)),
+        }
+    }
+}
+
+/// A table prepared for a keyed row diff: the columns read from both sides and which of them form the key
+struct KeyedTable {
+    name: String,
+    columns: Vec<String>,
+    key_indexes: Vec<usize>,
+    /// Compare keys with an explicit `COLLATE BINARY` (see `key_needs_collate`)
+    collate_keys: bool,
+}
+
+/// A slice of a table by key. `Between` bounds are lower-inclusive and upper-exclusive.
+enum KeyRange {
+    /// Rows with a NULL in any key column, which no `Between` range can match
+    NullKeys,
+    Between {
+        lower: Option<Vec<Value>>,
+        upper: Option<Vec<Value>>,
+    },
+}
+
+impl KeyRange {
+    /// Every row whose key has no NULLs
+    fn all() -> Self {
+        KeyRange::Between { lower: None, upper: None }
+    }
+}
+
+impl KeyedTable {
+    fn key_columns(&self) -> Vec<String> {
+        self.key_indexes.iter().map(|&i| self.columns[i].clone()).collect()
+    }
+
+    /// Key columns as SQL, with `COLLATE BINARY` when the table needs it (see `key_needs_collate`)
+    fn binary_keys(&self) -> Vec<String> {
+        self.key_columns().iter()
+            .map(|c| {
+                let column = DatabaseManager::quote_identifier(c);
+                if self.collate_keys { format!("{} COLLATE BINARY", column) } else { column }
+            })
+            .collect()
+    }
+
+    /// `SELECT columns FROM table [WHERE range] ORDER BY key`, plus the parameters to bind.
+    /// Keys are compared and ordered with BINARY collation so SQLite agrees with `compare_values`.
+    fn select(&self, columns: &[String], range: Option<&KeyRange>) -> (String, Vec<Value>) {
+        let (filter, params) = self.filter(range);
+        let select = format!(
+            "SELECT {} FROM {}{} ORDER BY {}",
+            columns.iter().map(|c| DatabaseManager::quote_identifier(c)).collect::<Vec<_>>().join(", "),
+            DatabaseManager::quote_identifier(&self.name),
+            filter,
+            self.binary_keys().join(", "),
+        );
+        (select, params)
+    }
+
+    /// The ` WHERE ...` clause selecting `range` (empty for the whole table), plus its parameters
+    fn filter(&self, range: Option<&KeyRange>) -> (String, Vec<Value>) {
+        let keys = self.key_columns();
+        let mut params = Vec::new();
+
+        let filter = match range {
+            None => String::new(),
+            Some(KeyRange::NullKeys) => format!(
+                " WHERE {}",
+                keys.iter().map(|c| format!("{} IS NULL", DatabaseManager::quote_identifier(c))).collect::<Vec<_>>().join(" OR ")
+            ),
+            Some(KeyRange::Between { lower, upper }) => {
+                let key_tuple = format!("({})", self.binary_keys().join(", "));
+                let placeholders = format!("({})", vec!["?"; keys.len()].join(", "));
+                let mut conditions: Vec<String> = keys.iter().map(|c| format!("{} IS NOT NULL", DatabaseManager::quote_identifier(c))).collect();
+                if let Some(lower) = lower {
+                    conditions.push(format!("{} >= {}", key_tuple, placeholders));
+                    params.extend(lower.iter().cloned());
+                }
+                if let Some(upper) = upper {
+                    conditions.push(format!("{} < {}", key_tuple, placeholders));
+                    params.extend(upper.iter().cloned());
+                }
+                format!(" WHERE {}", conditions.join(" AND "))
+            }
+        };
+        (filter, params)
+    }
+}
+
+/// SQL aggregate over the compared columns of a table: the wrapping sum of a hash of each
+/// row. A sum does not depend on the order SQLite visits the rows in, so the digest of a
+/// key range needs no `ORDER BY`. Both sides hash in the same process, so the hasher only
+/// has to be deterministic within a run.
+struct RowDigest;
+
+impl Aggregate<u64, i64> for RowDigest {
+    fn init(&self, _: &mut FunctionContext<'_>) -> RusqliteResult<u64> {
+        Ok(0)
+    }
+
+    fn step(&self, ctx: &mut FunctionContext<'_>, digest: &mut u64) -> RusqliteResult<()> {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::hash::DefaultHasher::new();
+        for i in 0..ctx.len() {
+            match ctx.get_raw(i) {
+                ValueRef::Null => 0u8.hash(&mut hasher),
+                ValueRef::Integer(i) => (1u8, i).hash(&mut hasher),
+                // Integral reals hash like integers because `compare_values` treats 1 and 1.0 as equal
+                ValueRef::Real(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => (1u8, f as i64).hash(&mut hasher),
+                ValueRef::Real(f) => (2u8, f.to_bits()).hash(&mut hasher),
+                ValueRef::Text(s) => (3u8, s).hash(&mut hasher),
+                ValueRef::Blob(b) => (4u8, b).hash(&mut hasher),
+            }
+        }
+        *digest = digest.wrapping_add(hasher.finish());
+        Ok(())
+    }
+
+    fn finalize(&self, _: &mut FunctionContext<'_>, digest: Option<u64>) -> RusqliteResult<i64> {
+        Ok(digest.unwrap_or(0) as i64)
+    }
+}
+
+/// Receives the rows of a keyed merge of two tables, in key order
+trait RowDiffSink {
+    /// A row only in database2
+    fn added(&mut self, row: Vec<Value>);
+    /// A row only in database1
+    fn removed(&mut self, row: Vec<Value>);
+    /// The rows with the same key on both sides, whether or not they differ
+    fn matched(&mut self, old: Vec<Value>, new: Vec<Value>);
+    /// `rows` rows found identical on both sides by digest, without reading them
+    fn identical(&mut self, rows: u64);
+    fn progress(&mut self) -> &mut DiffProgress;
+}
+
+/// Row-level progress of one table's merge; `processed` counts rows read from either side
+struct DiffProgress {
+    table_name: String,
+    processed: u64,
+    total: u64,
+    next_report: u64,
+}
+
+impl DiffProgress {
+    fn new(table_name: &str, total: u64) -> Self {
+        Self {
+            table_name: table_name.to_string(),
+            processed: 0,
+            total,
+            next_report: 0,
+        }
+    }
+
+    fn advance(&mut self, rows: u64) {
+        self.processed += rows;
+    }
+
+    /// Fail fast on cancellation and report progress every `ROW_PROGRESS_INTERVAL` rows
+    fn checkpoint(&mut self, cancel: &CancelToken) -> anyhow::Result<()> {
+        if self.processed >= self.next_report {
+            cancel.check()?;
+            cancel.report(ProgressPhase::ComparingRows, None, Some(&self.table_name), self.processed, self.total.max(self.processed));
+            self.next_report = self.processed + ROW_PROGRESS_INTERVAL;
+        }
+        Ok(())
+    }
+
+    fn report_done(&self, cancel: &CancelToken) {
+        cancel.report(ProgressPhase::ComparingRows, None, Some(&self.table_name), self.processed, self.processed);
+    }
+}
+
+/// Accumulates the result of a keyed row diff, keeping at most `max_rows` rows per list
+struct RowDiffCollector<'a> {
+    table: &'a KeyedTable,
+    max_rows: usize,
+    added_rows: Vec<serde_json::Value>,
+    removed_rows: Vec<serde_json::Value>,
+    changed_rows: Vec<RowDiff>,
+    summary: DataComparisonSummary,
+    progress: DiffProgress,
+}
+
+impl<'a> RowDiffCollector<'a> {
+    fn new(table: &'a KeyedTable, max_rows: Option<usize>, progress: DiffProgress) -> Self {
+        Self {
+            table,
+            max_rows: max_rows.unwrap_or(usize::MAX),
+            added_rows: Vec::new(),
+            removed_rows: Vec::new(),
+            changed_rows: Vec::new(),
+            summary: DataComparisonSummary::default(),
+            progress,
+        }
+    }
+
+    fn row_object(&self, row: &[Value]) -> serde_json::Value {
+        self.table.columns.iter().zip(row)
+            .map(|(column, value)| (column.clone(), DatabaseManager::value_to_json(value)))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+
+    fn finish(self, database1: String, database2: String) -> DataComparison {
+        DataComparison {
+            table_name: self.table.name.clone(),
+            database1,
+            database2,
+            key_columns: self.table.key_columns(),
+            compared_columns: self.table.columns.clone(),
+            added_rows: self.added_rows,
+            removed_rows: self.removed_rows,
+            changed_rows: self.changed_rows,
+            summary: self.summary,
+        }
+    }
+}
+
+impl RowDiffSink for RowDiffCollector<'_> {
+    fn added(&mut self, row: Vec<Value>) {
+        self.summary.database2_rows += 1;
+        self.summary.added += 1;
+        if self.added_rows.len() < self.max_rows {
+            self.added_rows.push(self.row_object(&row));
+        } else {
+            self.summary.truncated = true;
+        }
+    }
+
+    fn removed(&mut self, row: Vec<Value>) {
+        self.summary.database1_rows += 1;
+        self.summary.removed += 1;
+        if self.removed_rows.len() < self.max_rows {
+            self.removed_rows.push(self.row_object(&row));
+        } else {
+            self.summary.truncated = true;
+        }
+    }
+
+    fn matched(&mut self, old: Vec<Value>, new: Vec<Value>) {
+        self.summary.database1_rows += 1;
+        self.summary.database2_rows += 1;
+
+        let changes: Vec<CellChange> = self.table.columns.iter().enumerate()
+            .filter(|(i, _)| DatabaseManager::compare_values(&old[*i], &new[*i]).is_ne())
+            .map(|(i, column)| CellChange {
+                column: column.clone(),
+                old_value: DatabaseManager::value_to_json(&old[i]),
+                new_value: DatabaseManager::value_to_json(&new[i]),
+            })
+            .collect();
+
+        if changes.is_empty() {
+            self.summary.identical += 1;
+            return;
+        }
+
+        self.summary.changed += 1;
+        if self.changed_rows.len() < self.max_rows {
+            let key = self.table.key_indexes.iter()
+                .map(|&i| (self.table.columns[i].clone(), DatabaseManager::value_to_json(&old[i])))
+                .collect::<serde_json::Map<_, _>>()
+                .into();
+            self.changed_rows.push(RowDiff { key, changes });
+        } else {
+            self.summary.truncated = true;
+        }
+    }
+
+    fn identical(&mut self, rows: u64) {
+        self.summary.database1_rows += rows;
+        self.summary.database2_rows += rows;
+        self.summary.identical += rows;
+    }
+
+    fn progress(&mut self) -> &mut DiffProgress {
+        &mut self.progress
+    }
+}
+
+/// Statements that bring one table of the target in line with the source. Deletes run
+/// first so re-inserted rows cannot collide with rows that are about to go.
+struct TableSync {
+    table_name: String,
+    deletes: Vec<String>,
+    updates: Vec<String>,
+    inserts: Vec<String>,
+}
+
+impl TableSync {
+    fn statements(&self) -> impl Iterator<Item = &String> {
+        self.deletes.iter().chain(&self.updates).chain(&self.inserts)
+    }
+
+    fn summary(&self) -> TableSyncSummary {
+        TableSyncSummary {
+            table_name: self.table_name.clone(),
+            inserts: self.inserts.len() as u64,
+            updates: self.updates.len() as u64,
+            deletes: self.deletes.len() as u64,
+        }
+    }
+}
+
+/// One row of `PRAGMA foreign_key_check`; `rowid` is NULL for WITHOUT ROWID tables
+#[derive(Debug, PartialEq, Eq)]
+struct ForeignKeyViolation {
+    table_name: String,
+    rowid: Option<i64>,
+    parent: String,
+    foreign_key_id: i64,
+}
+
+impl ForeignKeyViolation {
+    fn into_conflict(self) -> SyncConflict {
+        let row = self.rowid.map(|rowid| format!("row {}", rowid)).unwrap_or_else(|| "a row".to_string());
+        SyncConflict {
+            message: format!(
+                "FOREIGN KEY constraint failed: {} of {} references a missing row in {} (foreign key {})",
+                row, self.table_name, self.parent, self.foreign_key_id
+            ),
+            table_name: self.table_name,
+            statement: None,
+        }
+    }
+}
+
+/// Turns a keyed merge of source (database1 side) and target (database2 side) into a `TableSync`
+struct SyncPlanner<'a> {
+    table: &'a KeyedTable,
+    options: &'a SyncOptions,
+    sync: TableSync,
+    progress: DiffProgress,
+}
+
+impl<'a> SyncPlanner<'a> {
+    fn new(table: &'a KeyedTable, options: &'a SyncOptions, progress: DiffProgress) -> Self {
+        Self {
+            table,
+            options,
+            sync: TableSync {
+                table_name: table.name.clone(),
+                deletes: Vec::new(),
+                updates: Vec::new(),
+                inserts: Vec::new(),
+            },
+            progress,
+        }
+    }
+
+    /// WHERE clause matching the row with `row`'s key
+    fn key_condition(&self, row: &[Value]) -> String {
+        self.table.key_indexes.iter()
+            .map(|&i| {
+                let column = DatabaseManager::quote_identifier(&self.table.columns[i]);
+                match &row[i] {
+                    Value::Null => format!("{} IS NULL", column),
+                    value => format!("{} = {}", column, DatabaseManager::sql_literal(value)),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    }
+}
+
+impl RowDiffSink for SyncPlanner<'_> {
+    /// Only in the target: delete it
+    fn added(&mut self, row: Vec<Value>) {
+        if self.options.include_deletes {
+            self.sync.deletes.push(format!(
+                "DELETE FROM {} WHERE {}",
+                DatabaseManager::quote_identifier(&self.table.name),
+                self.key_condition(&row)
+            ));
+        }
+    }
+
+    /// Only in the source: insert it
+    fn removed(&mut self, row: Vec<Value>) {
+        if self.options.include_inserts {
+            self.sync.inserts.push(format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                DatabaseManager::quote_identifier(&self.table.name),
+                self.table.columns.iter().map(|c| DatabaseManager::quote_identifier(c)).collect::<Vec<_>>().join(", "),
+                row.iter().map(DatabaseManager::sql_literal).collect::<Vec<_>>().join(", ")
+            ));
+        }
+    }
+
+    /// In both: copy the source's values over the target's where they differ
+    fn matched(&mut self, source: Vec<Value>, target: Vec<Value>) {
+        if !self.options.include_updates {
+            return;
+        }
+        let assignments: Vec<String> = self.table.columns.iter().enumerate()
+            .filter(|(i, _)| DatabaseManager::compare_values(&source[*i], &target[*i]).is_ne())
+            .map(|(i, column)| format!("{} = {}", DatabaseManager::quote_identifier(column), DatabaseManager::sql_literal(&source[i])))
+            .collect();
+        if !assignments.is_empty() {
+            self.sync.updates.push(format!(
+                "UPDATE {} SET {} WHERE {}",
+                DatabaseManager::quote_identifier(&self.table.name),
+                assignments.join(", "),
+                self.key_condition(&target)
+            ));
+        }
+    }
+
+    fn identical(&mut self, _rows: u64) {}
+
+    fn progress(&mut self) -> &mut DiffProgress {
+        &mut self.progress
+    }
+}
+
+/// Entries of `sqlite_master`, read once per migration
+struct Schema {
+    objects: Vec<SchemaObject>,
+}
+
+struct SchemaObject {
+    kind: String,
+    name: String,
+    table_name: String,
+    sql: Option<String>,
+}
+
+impl Schema {
+    fn read(conn: &Connection) -> anyhow::Result<Self> {
+        let mut stmt = conn.prepare("SELECT type, name, tbl_name, sql FROM sqlite_master ORDER BY type, name")?;
+        let objects = stmt.query_map([], |row| {
+            Ok(SchemaObject {
+                kind: row.get(0)?,
+                name: row.get(1)?,
+                table_name: row.get(2)?,
+                sql: row.get(3)?,
+            })
+        })?.collect::<RusqliteResult<Vec<_>>>()?;
+        Ok(Schema { objects })
+    }
+
+    fn table_sql(&self, table_name: &str) -> Option<&str> {
+        self.objects.iter()
+            .find(|o| o.kind == "table" && o.name.eq_ignore_ascii_case(table_name))
+            .and_then(|o| o.sql.as_deref())
+    }
+
+    fn object(&self, kind: SchemaObjectKind, name: &str) -> Option<&SchemaObject> {
+        self.objects.iter().find(|o| o.kind == object_type(kind) && o.name.eq_ignore_ascii_case(name))
+    }
+
+    /// `CREATE INDEX`/`CREATE TRIGGER` statements for a table; automatic indexes have no SQL
+    fn dependents<'a>(&'a self, table_name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.objects.iter()
+            .filter(move |o| matches!(o.kind.as_str(), "index" | "trigger") && o.table_name.eq_ignore_ascii_case(table_name))
+            .filter_map(|o| o.sql.as_deref())
+    }
+}
+
+/// The `sqlite_master.type` of an object kind
+fn object_type(kind: SchemaObjectKind) -> &'static str {
+    match kind {
+        SchemaObjectKind::Index => "index",
+        SchemaObjectKind::Trigger => "trigger",
+        SchemaObjectKind::View => "view",
+    }
+}
+
+/// What every table of a migration is migrated with
+#[derive(Clone, Copy)]
+struct MigrationContext<'a> {
+    conn1: &'a Connection,
+    conn2: &'a Connection,
+    schema1: &'a Schema,
+    schema2: &'a Schema,
+    /// Lower-cased names of the indexes, triggers and views dropped before the tables change
+    dropped: &'a std::collections::HashSet<String>,
+    /// Column renames the user confirmed, for every table
+    renames: &'a [ColumnRename],
+}
+
+/// Statements of a migration in the order they run, each step headed by a comment
+#[derive(Default)]
+struct MigrationPlan {
+    tables: Vec<TableMigration>,
+    objects: Vec<ObjectMigration>,
+    steps: Vec<(String, Vec<String>)>,
+    warnings: Vec<String>,
+}
+
+impl MigrationPlan {
+    fn push(&mut self, table_name: &str, action: MigrationAction, reasons: Vec<String>, statements: Vec<String>) {
+        let action_name = match action {
+            MigrationAction::Create => "create",
+            MigrationAction::Drop => "drop",
+            MigrationAction::Alter => "alter",
+            MigrationAction::Rebuild => "rebuild",
+            MigrationAction::Replace => "replace",
+        };
+        let mut comment = format!("{}: {}", table_name, action_name);
+        if !reasons.is_empty() {
+            comment.push_str(&format!(" ({})", reasons.join("; ")));
+        }
+        self.tables.push(TableMigration { table_name: table_name.to_string(), action, reasons });
+        self.steps.push((comment, statements));
+    }
+
+    /// Drop an index, trigger or view; `replaced` ones are created again by `create_object`
+    fn drop_object(&mut self, kind: SchemaObjectKind, name: &str, replaced: bool) {
+        let action = if replaced { MigrationAction::Replace } else { MigrationAction::Drop };
+        self.objects.push(ObjectMigration { kind, name: name.to_string(), action });
+        self.steps.push((
+            format!("{} {}: drop", object_type(kind), name),
+            vec![format!("DROP {} {}", object_type(kind).to_uppercase(), DatabaseManager::quote_identifier(name))],
+        ));
+    }
+
+    fn create_object(&mut self, kind: SchemaObjectKind, name: &str, sql: &str, replaced: bool) {
+        if !replaced {
+            self.objects.push(ObjectMigration { kind, name: name.to_string(), action: MigrationAction::Create });
+        }
+        self.steps.push((format!("{} {}: create", object_type(kind), name), vec![sql.to_string()]));
+    }
+
+    fn append(&mut self, other: MigrationPlan) {
+        self.tables.extend(other.tables);
+        self.objects.extend(other.objects);
+        self.steps.extend(other.steps);
+        self.warnings.extend(other.warnings);
+    }
+
+    fn script(&self, source: &str, target: &str) -> String {
+        let mut sql = format!("-- Schema migration script\n-- Source: {}\n-- Target: {}\n", source, target);
+        if self.steps.is_empty() {
+            sql.push_str("\n-- The schemas match; nothing to migrate\n");
+            return sql;
+        }
+
+        let rebuilds = self.tables.iter().any(|t| t.action == MigrationAction::Rebuild);
+        // Foreign keys must be off while tables are dropped and rebuilt; this only takes effect
+        // outside a transaction
+        sql.push_str("\nPRAGMA foreign_keys = OFF;\n");
+        if rebuilds {
+            // Renaming a rebuilt table back would otherwise fail on views and triggers that
+            // reference it, since the original is already gone
+            sql.push_str("PRAGMA legacy_alter_table = ON;\n");
+        }
+        sql.push_str("BEGIN TRANSACTION;\n");
+
+        for (comment, statements) in &self.steps {
+            sql.push_str(&format!("\n-- {}\n", comment));
+            for statement in statements {
+                sql.push_str(statement);
+                sql.push_str(";\n");
+            }
+        }
+
+        sql.push_str("\n-- Any rows returned here are foreign keys the migrated data no longer satisfies\n");
+        sql.push_str("PRAGMA foreign_key_check;\nCOMMIT;\n");
+        if rebuilds {
+            sql.push_str("PRAGMA legacy_alter_table = OFF;\n");
+        }
+        // foreign_keys belongs to the connection, not the file, and a script cannot save and
+        // restore it, so it ends with enforcement on and says so
+        sql.push_str("\n-- Turns foreign key enforcement on for this connection, whether or not it was on before;\n");
+        sql.push_str("-- drop this line if the connection running the script keeps it off\n");
+        sql.push_str("PRAGMA foreign_keys = ON;\n");
+        sql
+    }
+}
@@ -0,0 +1,489 @@
+//! Schema comparison over the models `DatabaseManager` reads. Nothing here touches a
+//! database, so schemas from two connections, or from a connection and a snapshot, compare
+//! the same way.
+
+use crate::models::*;
+use crate::sql::{describe_checks, describe_index_terms, same_checks, same_optional_sql, same_sql};
+use std::collections::BTreeSet;
+
+/// Compare two schemas; `database1` and `database2` label them in the result. Anything only
+/// in `schema2` is reported as added, anything only in `schema1` as removed. `on_table` is
+/// called with each table present in both schemas, just before it is compared.
+pub fn compare_schemas(
+    schema1: &DatabaseSchema,
+    schema2: &DatabaseSchema,
+    (database1, database2): (String, String),
+    options: &SchemaCompareOptions,
+    mut on_table: impl FnMut(&str),
+) -> SchemaComparison {
+    let (tables1, tables2) = (&schema1.tables, &schema2.tables);
+
+    // Ordered sets, so the table lists come out sorted by name
+    let table1_names: BTreeSet<String> = tables1.iter().map(|t| t.name.clone()).collect();
+    let table2_names: BTreeSet<String> = tables2.iter().map(|t| t.name.clone()).collect();
+
+    let added_tables: Vec<String> = table2_names.difference(&table1_names).cloned().collect();
+    let removed_tables: Vec<String> = table1_names.difference(&table2_names).cloned().collect();
+    
+    let common_tables: Vec<String> = table1_names.intersection(&table2_names).cloned().collect();
+    let mut modified_tables = Vec::new();
+    let mut identical_tables = Vec::new();
+
+    for table_name in common_tables {
+        on_table(&table_name);
+        let table1 = tables1.iter().find(|t| t.name == table_name).unwrap();
+        let table2 = tables2.iter().find(|t| t.name == table_name).unwrap();
+        
+        if tables_are_identical(table1, table2, options) {
+            identical_tables.push(table_name);
+        } else {
+            let diff = compare_tables(table1, table2, options);
+            modified_tables.push(diff);
+        }
+    }
+
+    let (added_indexes, removed_indexes, modified_indexes) =
+        compare_objects(&schema1.indexes, &schema2.indexes, |i| &i.name, index_changes);
+    let (added_triggers, removed_triggers, modified_triggers) =
+        compare_objects(&schema1.triggers, &schema2.triggers, |t| &t.name, trigger_changes);
+    let (added_views, removed_views, modified_views) =
+        compare_objects(&schema1.views, &schema2.views, |v| &v.name, view_changes);
+
+    SchemaComparison {
+        database1,
+        database2,
+        added_tables,
+        removed_tables,
+        modified_tables,
+        identical_tables,
+        added_indexes,
+        removed_indexes,
+        modified_indexes,
+        added_triggers,
+        removed_triggers,
+        modified_triggers,
+        added_views,
+        removed_views,
+        modified_views,
+        pragma_changes: pragma_changes(&schema1.pragmas, &schema2.pragmas),
+    }
+}
+
+fn pragma_changes(pragmas1: &SchemaPragmas, pragmas2: &SchemaPragmas) -> Vec<String> {
+    let auto_vacuum = |mode: i64| match mode {
+        0 => "none".to_string(),
+        1 => "full".to_string(),
+        2 => "incremental".to_string(),
+        other => other.to_string(),
+    };
+    let mut changes = Vec::new();
+    if pragmas1.user_version != pragmas2.user_version {
+        changes.push(format!("user_version: {} -> {}", pragmas1.user_version, pragmas2.user_version));
+    }
+    if pragmas1.application_id != pragmas2.application_id {
+        changes.push(format!("application_id: {} -> {}", pragmas1.application_id, pragmas2.application_id));
+    }
+    if !pragmas1.encoding.eq_ignore_ascii_case(&pragmas2.encoding) {
+        changes.push(format!("encoding: {} -> {}", pragmas1.encoding, pragmas2.encoding));
+    }
+    if pragmas1.auto_vacuum != pragmas2.auto_vacuum {
+        changes.push(format!("auto_vacuum: {} -> {}", auto_vacuum(pragmas1.auto_vacuum), auto_vacuum(pragmas2.auto_vacuum)));
+    }
+    changes
+}
+
+/// Match named objects of both schemas: (added, removed, modified)
+fn compare_objects<T>(
+    objects1: &[T],
+    objects2: &[T],
+    name: impl Fn(&T) -> &String,
+    changes: impl Fn(&T, &T) -> Vec<String>,
+) -> (Vec<String>, Vec<String>, Vec<ObjectDiff>) {
+    let added = objects2.iter()
+        .filter(|new| !objects1.iter().any(|old| name(old) == name(new)))
+        .map(|new| name(new).clone())
+        .collect();
+    let mut removed = Vec::new();
+    let mut modified = Vec::new();
+    for old in objects1 {
+        match objects2.iter().find(|new| name(new) == name(old)) {
+            None => removed.push(name(old).clone()),
+            Some(new) => {
+                let changes = changes(old, new);
+                if !changes.is_empty() {
+                    modified.push(ObjectDiff { name: name(old).clone(), changes });
+                }
+            }
+        }
+    }
+    (added, removed, modified)
+}
+
+fn index_changes(old: &IndexInfo, new: &IndexInfo) -> Vec<String> {
+    let mut changes = Vec::new();
+    if !old.table_name.eq_ignore_ascii_case(&new.table_name) {
+        changes.push(format!("table: {} -> {}", old.table_name, new.table_name));
+    }
+    if old.is_unique != new.is_unique {
+        changes.push(format!("unique: {} -> {}", old.is_unique, new.is_unique));
+    }
+    let same_term = |a: &IndexColumn, b: &IndexColumn| {
+        a.name.as_deref().map(str::to_lowercase) == b.name.as_deref().map(str::to_lowercase)
+            && same_optional_sql(&a.expression, &b.expression)
+            && a.descending == b.descending
+            && a.collation.eq_ignore_ascii_case(&b.collation)
+    };
+    if old.columns.len() != new.columns.len() || !old.columns.iter().zip(&new.columns).all(|(a, b)| same_term(a, b)) {
+        changes.push(format!("columns: ({}) -> ({})", describe_index_terms(&old.columns), describe_index_terms(&new.columns)));
+    }
+    if !same_optional_sql(&old.where_clause, &new.where_clause) {
+        changes.push(format!(
+            "where: {} -> {}",
+            old.where_clause.as_deref().unwrap_or("none"),
+            new.where_clause.as_deref().unwrap_or("none")
+        ));
+    }
+    changes
+}
+
+fn trigger_changes(old: &TriggerInfo, new: &TriggerInfo) -> Vec<String> {
+    let mut changes = Vec::new();
+    if !old.table_name.eq_ignore_ascii_case(&new.table_name) {
+        changes.push(format!("table: {} -> {}", old.table_name, new.table_name));
+    }
+    if !same_sql(&old.sql, &new.sql) {
+        changes.push("definition changed".to_string());
+    }
+    changes
+}
+
+fn view_changes(old: &ViewInfo, new: &ViewInfo) -> Vec<String> {
+    let mut changes = Vec::new();
+    if old.columns != new.columns {
+        changes.push(format!("columns: ({}) -> ({})", old.columns.join(", "), new.columns.join(", ")));
+    }
+    if !same_sql(&old.sql, &new.sql) {
+        changes.push("definition changed".to_string());
+    }
+    changes
+}
+
+/// Identical means nothing for `compare_tables` to report, column order included
+pub fn tables_are_identical(table1: &TableInfo, table2: &TableInfo, options: &SchemaCompareOptions) -> bool {
+    let diff = compare_tables(table1, table2, options);
+    diff.added_columns.is_empty()
+        && diff.removed_columns.is_empty()
+        && diff.modified_columns.is_empty()
+        && diff.moved_columns.is_empty()
+        && diff.added_foreign_keys.is_empty()
+        && diff.removed_foreign_keys.is_empty()
+        && diff.modified_foreign_keys.is_empty()
+        && diff.changes.is_empty()
+}
+
+pub fn compare_tables(table1: &TableInfo, table2: &TableInfo, options: &SchemaCompareOptions) -> TableDiff {
+    let col1_names: std::collections::HashSet<String> = table1.columns.iter().map(|c| c.name.clone()).collect();
+    let col2_names: std::collections::HashSet<String> = table2.columns.iter().map(|c| c.name.clone()).collect();
+
+    let added_columns: Vec<ColumnInfo> = table2.columns.iter()
+        .filter(|c| !col1_names.contains(&c.name))
+        .cloned()
+        .collect();
+
+    let removed_columns: Vec<String> = table1.columns.iter()
+        .filter(|c| !col2_names.contains(&c.name))
+        .map(|c| c.name.clone())
+        .collect();
+
+    // Build name->ColumnInfo maps
+    let map1: std::collections::HashMap<_, _> = table1.columns.iter().map(|c| (c.name.clone(), c)).collect();
+    let map2: std::collections::HashMap<_, _> = table2.columns.iter().map(|c| (c.name.clone(), c)).collect();
+
+    // Columns that exist in both, in their order in table1
+    let common_cols: Vec<String> = table1.columns.iter()
+        .filter(|c| col2_names.contains(&c.name))
+        .map(|c| c.name.clone())
+        .collect();
+
+    let mut modified_columns = Vec::new();
+    for name in common_cols {
+        let c1 = map1.get(&name).unwrap();
+        let c2 = map2.get(&name).unwrap();
+
+        let changes = column_changes(c1, c2, options.type_comparison);
+        if !changes.is_empty() {
+            modified_columns.push(ColumnDiff{
+                column_name: name,
+                old_type: c1.data_type.clone(),
+                new_type: c2.data_type.clone(),
+                changes,
+                type_change: type_change(c1, c2),
+            });
+        }
+    }
+
+
+    let (added_foreign_keys, removed_foreign_keys, modified_foreign_keys) =
+        compare_foreign_keys(&table1.foreign_keys, &table2.foreign_keys);
+    let renamed_columns = renamed_columns(table1, table2, &removed_columns, &added_columns, options.type_comparison);
+
+    TableDiff {
+        table_name: table1.name.clone(),
+        added_columns,
+        removed_columns,
+        modified_columns,
+        added_foreign_keys,
+        removed_foreign_keys,
+        modified_foreign_keys,
+        changes: table_changes(table1, table2),
+        moved_columns: moved_columns(table1, table2),
+        renamed_columns,
+    }
+}
+
+/// Columns present in both tables that fall outside the longest common subsequence of their
+/// orders, so that moving one column to the end reports that column alone
+fn moved_columns(table1: &TableInfo, table2: &TableInfo) -> Vec<ColumnMove> {
+    let shared = |table: &TableInfo, other: &TableInfo| -> Vec<(usize, String)> {
+        table.columns.iter().enumerate()
+            .filter(|(_, c)| other.columns.iter().any(|o| o.name == c.name))
+            .map(|(position, c)| (position, c.name.clone()))
+            .collect()
+    };
+    let (old, new) = (shared(table1, table2), shared(table2, table1));
+
+    // lengths[i][j]: longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i].1 == new[j].1 {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut in_place = std::collections::HashSet::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i].1 == new[j].1 {
+            in_place.insert(new[j].1.clone());
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    new.iter()
+        .filter(|(_, name)| !in_place.contains(name))
+        .map(|(new_position, name)| ColumnMove {
+            column_name: name.clone(),
+            old_position: old.iter().find(|(_, n)| n == name).map(|(p, _)| *p).unwrap_or_default(),
+            new_position: *new_position,
+        })
+        .collect()
+}
+
+/// Pair removed with added columns that have the same definition and either the same
+/// position or a similar name. Each column takes part in one pair at most, best match first.
+fn renamed_columns(table1: &TableInfo, table2: &TableInfo, removed: &[String], added: &[ColumnInfo], types: TypeComparison) -> Vec<RenamedColumn> {
+    let mut candidates = Vec::new();
+    for (old_position, old) in table1.columns.iter().enumerate().filter(|(_, c)| removed.contains(&c.name)) {
+        for (new_position, new) in table2.columns.iter().enumerate().filter(|(_, c)| added.iter().any(|a| a.name == c.name)) {
+            if !column_changes(old, new, types).is_empty() {
+                continue;
+            }
+            let similarity = name_similarity(&old.name, &new.name);
+            if old_position == new_position || similarity >= 0.5 {
+                candidates.push(RenamedColumn {
+                    old_name: old.name.clone(),
+                    new_name: new.name.clone(),
+                    old_position,
+                    new_position,
+                    similarity,
+                });
+            }
+        }
+    }
+    candidates.sort_by(|a, b| {
+        (b.old_position == b.new_position).cmp(&(a.old_position == a.new_position))
+            .then(b.similarity.total_cmp(&a.similarity))
+    });
+
+    let mut renames: Vec<RenamedColumn> = Vec::new();
+    for candidate in candidates {
+        if !renames.iter().any(|r| r.old_name == candidate.old_name || r.new_name == candidate.new_name) {
+            renames.push(candidate);
+        }
+    }
+    renames.sort_by_key(|r| r.new_position);
+    renames
+}
+
+/// 1 minus the edit distance over the length of the longer name, ignoring case
+fn name_similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+/// How the declared type changed, if it did. Case and spacing are not changes.
+pub fn type_change(c1: &ColumnInfo, c2: &ColumnInfo) -> Option<TypeChange> {
+    if same_sql(&c1.data_type, &c2.data_type) {
+        None
+    } else if c1.affinity == c2.affinity {
+        Some(TypeChange::Cosmetic)
+    } else {
+        Some(TypeChange::Affinity)
+    }
+}
+
+/// Differences between two definitions of a column, ignoring its name
+pub fn column_changes(c1: &ColumnInfo, c2: &ColumnInfo, types: TypeComparison) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    match type_change(c1, c2) {
+        Some(TypeChange::Cosmetic) if types == TypeComparison::Declared => {
+            changes.push(format!("type: {} -> {} (cosmetic, {} affinity)", c1.data_type, c2.data_type, c1.affinity));
+        }
+        Some(TypeChange::Affinity) => {
+            changes.push(format!("type: {} -> {} (affinity {} -> {})", c1.data_type, c2.data_type, c1.affinity, c2.affinity));
+        }
+        _ => {}
+    }
+    if c1.is_nullable != c2.is_nullable {
+        changes.push(format!("nullability: {} -> {}", c1.is_nullable, c2.is_nullable));
+    }
+    if c1.is_primary_key != c2.is_primary_key {
+        changes.push(format!("primary key: {} -> {}", c1.is_primary_key, c2.is_primary_key));
+    }
+    if !same_optional_sql(&c1.default_value, &c2.default_value) {
+        changes.push(format!(
+            "default: {} -> {}",
+            c1.default_value.as_deref().unwrap_or("none"),
+            c2.default_value.as_deref().unwrap_or("none")
+        ));
+    }
+    let collation = |c: &ColumnInfo| c.collation.clone().unwrap_or_else(|| "BINARY".to_string());
+    if !collation(c1).eq_ignore_ascii_case(&collation(c2)) {
+        changes.push(format!("collation: {} -> {}", collation(c1), collation(c2)));
+    }
+    let same_generated = match (&c1.generated, &c2.generated) {
+        (Some(g1), Some(g2)) => g1.stored == g2.stored && same_sql(&g1.expression, &g2.expression),
+        (g1, g2) => g1 == g2,
+    };
+    if !same_generated {
+        let describe = |c: &ColumnInfo| match &c.generated {
+            Some(g) => format!("AS ({}) {}", g.expression, if g.stored { "STORED" } else { "VIRTUAL" }),
+            None => "none".to_string(),
+        };
+        changes.push(format!("generated: {} -> {}", describe(c1), describe(c2)));
+    }
+    if !same_checks(&c1.checks, &c2.checks) {
+        changes.push(format!("check: {} -> {}", describe_checks(&c1.checks), describe_checks(&c2.checks)));
+    }
+    changes
+}
+
+/// Differences in constraints and options that belong to the table rather than a column
+fn table_changes(table1: &TableInfo, table2: &TableInfo) -> Vec<String> {
+    let mut changes = Vec::new();
+    let lowercase = |columns: &[String]| columns.iter().map(|c| c.to_lowercase()).collect::<Vec<_>>();
+
+    // Membership changes show up on the columns; only a reordered key is reported here
+    let (key1, key2) = (lowercase(&table1.primary_key), lowercase(&table2.primary_key));
+    let (mut sorted1, mut sorted2) = (key1.clone(), key2.clone());
+    sorted1.sort();
+    sorted2.sort();
+    if key1 != key2 && sorted1 == sorted2 {
+        changes.push(format!("primary key: ({}) -> ({})", table1.primary_key.join(", "), table2.primary_key.join(", ")));
+    }
+
+    let unique = |table: &TableInfo| {
+        let mut constraints: Vec<Vec<String>> = table.unique_constraints.iter().map(|c| lowercase(c)).collect();
+        constraints.sort();
+        constraints
+    };
+    if unique(table1) != unique(table2) {
+        let describe = |table: &TableInfo| match table.unique_constraints.as_slice() {
+            [] => "none".to_string(),
+            constraints => constraints.iter().map(|c| format!("({})", c.join(", "))).collect::<Vec<_>>().join(", "),
+        };
+        changes.push(format!("unique: {} -> {}", describe(table1), describe(table2)));
+    }
+    if !same_checks(&table1.checks, &table2.checks) {
+        changes.push(format!("check: {} -> {}", describe_checks(&table1.checks), describe_checks(&table2.checks)));
+    }
+    if table1.autoincrement != table2.autoincrement {
+        changes.push(format!("autoincrement: {} -> {}", table1.autoincrement, table2.autoincrement));
+    }
+    if table1.without_rowid != table2.without_rowid {
+        changes.push(format!("without rowid: {} -> {}", table1.without_rowid, table2.without_rowid));
+    }
+    if table1.strict != table2.strict {
+        changes.push(format!("strict: {} -> {}", table1.strict, table2.strict));
+    }
+    changes
+}
+
+/// Foreign keys are matched on their child columns: (added, removed, modified)
+fn compare_foreign_keys(old: &[ForeignKeyInfo], new: &[ForeignKeyInfo]) -> (Vec<ForeignKeyInfo>, Vec<ForeignKeyInfo>, Vec<ForeignKeyDiff>) {
+    let same_columns = |a: &ForeignKeyInfo, b: &ForeignKeyInfo| {
+        a.columns.len() == b.columns.len() && a.columns.iter().zip(&b.columns).all(|(a, b)| a.eq_ignore_ascii_case(b))
+    };
+    let added = new.iter().filter(|n| !old.iter().any(|o| same_columns(o, n))).cloned().collect();
+    let mut removed = Vec::new();
+    let mut modified = Vec::new();
+    for o in old {
+        let Some(n) = new.iter().find(|n| same_columns(o, n)) else {
+            removed.push(o.clone());
+            continue;
+        };
+        let references = |key: &ForeignKeyInfo| {
+            if key.referenced_columns.is_empty() {
+                key.referenced_table.clone()
+            } else {
+                format!("{}({})", key.referenced_table, key.referenced_columns.join(", "))
+            }
+        };
+        let deferral = |key: &ForeignKeyInfo| match (key.deferrable, key.initially_deferred) {
+            (true, true) => "initially deferred",
+            (true, false) => "deferrable",
+            (false, _) => "not deferrable",
+        };
+
+        let mut changes = Vec::new();
+        if !references(o).eq_ignore_ascii_case(&references(n)) {
+            changes.push(format!("references: {} -> {}", references(o), references(n)));
+        }
+        if o.on_update != n.on_update {
+            changes.push(format!("on update: {} -> {}", o.on_update, n.on_update));
+        }
+        if o.on_delete != n.on_delete {
+            changes.push(format!("on delete: {} -> {}", o.on_delete, n.on_delete));
+        }
+        if deferral(o) != deferral(n) {
+            changes.push(format!("deferrable: {} -> {}", deferral(o), deferral(n)));
+        }
+        if !changes.is_empty() {
+            modified.push(ForeignKeyDiff { columns: o.columns.clone(), changes });
+        }
+    }
+    (added, removed, modified)
+}
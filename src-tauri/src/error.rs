@@ -0,0 +1,126 @@
+use rusqlite::ErrorCode;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+
+/// Error returned by every Tauri command. The payload is the detail text; the frontend
+/// receives `{ code, message, details }` so it can branch on `code` instead of parsing strings.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AppError {
+    #[error("Wrong key or cipher settings: {0}")]
+    WrongKey(String),
+    #[error("Not a SQLite or SQLCipher database: {0}")]
+    NotADatabase(String),
+    #[error("File not found: {0}")]
+    FileNotFound(String),
+    #[error("Database is busy or locked: {0}")]
+    Busy(String),
+    #[error("Operation timed out: {0}")]
+    Timeout(String),
+    #[error("Operation cancelled")]
+    Cancelled,
+    #[error("Table not found: {0}")]
+    TableNotFound(String),
+    #[error("Database not connected: {0}")]
+    NotConnected(String),
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    #[error("Database error: {0}")]
+    Database(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AppError {
+    /// Stable machine-readable code for the frontend
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::WrongKey(_) => "WRONG_KEY",
+            AppError::NotADatabase(_) => "NOT_A_DATABASE",
+            AppError::FileNotFound(_) => "FILE_NOT_FOUND",
+            AppError::Busy(_) => "BUSY",
+            AppError::Timeout(_) => "TIMEOUT",
+            AppError::Cancelled => "CANCELLED",
+            AppError::TableNotFound(_) => "TABLE_NOT_FOUND",
+            AppError::NotConnected(_) => "NOT_CONNECTED",
+            AppError::InvalidInput(_) => "INVALID_INPUT",
+            AppError::Database(_) => "DATABASE_ERROR",
+            AppError::Internal(_) => "INTERNAL",
+        }
+    }
+
+    /// Short human summary, without the detail text
+    pub fn summary(&self) -> &'static str {
+        match self {
+            AppError::WrongKey(_) => "Wrong key or cipher settings",
+            AppError::NotADatabase(_) => "Not a SQLite or SQLCipher database",
+            AppError::FileNotFound(_) => "File not found",
+            AppError::Busy(_) => "Database is busy or locked",
+            AppError::Timeout(_) => "Operation timed out",
+            AppError::Cancelled => "Operation cancelled",
+            AppError::TableNotFound(_) => "Table not found",
+            AppError::NotConnected(_) => "Database not connected",
+            AppError::InvalidInput(_) => "Invalid input",
+            AppError::Database(_) => "Database error",
+            AppError::Internal(_) => "Internal error",
+        }
+    }
+
+    pub fn details(&self) -> Option<&str> {
+        match self {
+            AppError::WrongKey(d)
+            | AppError::NotADatabase(d)
+            | AppError::FileNotFound(d)
+            | AppError::Busy(d)
+            | AppError::Timeout(d)
+            | AppError::TableNotFound(d)
+            | AppError::NotConnected(d)
+            | AppError::InvalidInput(d)
+            | AppError::Database(d)
+            | AppError::Internal(d) => Some(d.as_str()).filter(|d| !d.is_empty()),
+            AppError::Cancelled => None,
+        }
+    }
+
+    /// Classify a SQLite failure that was not raised as an `AppError` by the manager
+    fn from_sqlite(err: &rusqlite::Error, details: String) -> Self {
+        match err.sqlite_error_code() {
+            Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => AppError::Busy(details),
+            Some(ErrorCode::NotADatabase) => AppError::NotADatabase(details),
+            Some(ErrorCode::OperationInterrupted) => AppError::Cancelled,
+            Some(ErrorCode::CannotOpen) => AppError::FileNotFound(details),
+            _ => AppError::Database(details),
+        }
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(err: anyhow::Error) -> Self {
+        // Full context chain, e.g. "Failed to apply cipher settings: ..."
+        let details = format!("{:#}", err);
+
+        if let Some(app) = err.chain().find_map(|e| e.downcast_ref::<AppError>()) {
+            return app.clone();
+        }
+        if let Some(sqlite) = err.chain().find_map(|e| e.downcast_ref::<rusqlite::Error>()) {
+            return AppError::from_sqlite(sqlite, details);
+        }
+        if let Some(io) = err.chain().find_map(|e| e.downcast_ref::<std::io::Error>()) {
+            if io.kind() == std::io::ErrorKind::NotFound {
+                return AppError::FileNotFound(details);
+            }
+        }
+
+        AppError::Internal(details)
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", self.summary())?;
+        state.serialize_field("details", &self.details())?;
+        state.end()
+    }
+}
@@ -1,14 +1,12 @@
-// Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
-#[tauri::command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
-}
+//! Core of the SQLCipher comparison tool, shared by the desktop app and the command-line
+//! binary. `DatabaseManager` opens plaintext and encrypted databases and reads their schema
+//! and data; `diff` compares schemas without touching a database.
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![greet])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
-}
+pub mod database;
+pub mod diff;
+pub mod error;
+pub mod models;
+mod sql;
+
+pub use database::{CancelToken, DatabaseManager, ProgressSink};
+pub use error::AppError;
@@ -1,17 +1,15 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod models;
-mod database;
 mod commands;
 
-use database::DatabaseManager;
-use std::sync::Mutex;
+use sqlcipher_tool::DatabaseManager;
+use std::sync::Arc;
 use tauri_plugin_dialog;
 
 fn main() {
     // Initialize database manager
-    let db_manager = Mutex::new(DatabaseManager::new());
+    let db_manager = Arc::new(DatabaseManager::new());
     
     tauri::Builder::default()
         // .plugin(tauri_plugin_opener::init())
@@ -22,9 +20,20 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             commands::test_connection,
             commands::connect_database,
+            commands::disconnect_database,
+            commands::disconnect_all,
+            commands::list_connections,
+            commands::reconnect_database,
             commands::get_database_tables,
             commands::get_table_data,
             commands::compare_database_schemas,
+            commands::export_schema_snapshot,
+            commands::compare_schema_with_snapshot,
+            commands::generate_migration_script,
+            commands::compare_table_data,
+            commands::generate_sync_script,
+            commands::apply_sync,
+            commands::cancel_operation,
         ])
         .run(tauri::generate_context!())
                 .expect("error while running tauri application");
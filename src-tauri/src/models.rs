@@ -1,13 +1,98 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Opaque handle issued by `connect_database`; the same file can be open under several ids
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ConnectionId(pub String);
+
+impl std::fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseInfo {
+    pub id: ConnectionId,
+    /// Canonical path of the database file
     pub path: String,
     pub name: String,
     pub table_count: i32,
     pub is_connected: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub alias: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher_settings: Option<CipherSettings>,
+    /// Name of the profile that opened the file when cipher detection was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher_profile: Option<String>,
+    pub open_mode: OpenMode,
+    /// Unix timestamp (milliseconds) when the connection was opened
+    pub opened_at: u64,
+}
+
+/// How a database file is opened. Inspection is read-only unless writes are explicitly requested.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenMode {
+    #[default]
+    ReadOnly,
+    /// Read-only with `immutable=1`: no locks, no -wal/-shm files; only safe for files nothing else is writing
+    Immutable,
+    ReadWrite,
+}
+
+/// Key supplied for an encrypted database. Never serialized back to the frontend.
+#[derive(Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum KeyMaterial {
+    Passphrase { passphrase: String },
+    /// 32-byte key as 64 hex characters, used without key derivation
+    RawKey { key_hex: String },
+    /// 32-byte key plus the 16-byte database salt, as 64 + 32 hex characters
+    RawKeyWithSalt { key_hex: String, salt_hex: String },
+    /// Key read from a local file: a passphrase, or with `raw` a binary/hex raw key
+    KeyFile {
+        path: String,
+        #[serde(default)]
+        raw: bool,
+    },
+}
+
+impl std::fmt::Debug for KeyMaterial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyMaterial::Passphrase { .. } => write!(f, "Passphrase(****)"),
+            KeyMaterial::RawKey { .. } => write!(f, "RawKey(****)"),
+            KeyMaterial::RawKeyWithSalt { .. } => write!(f, "RawKeyWithSalt(****)"),
+            KeyMaterial::KeyFile { path, raw } => write!(f, "KeyFile({}, raw: {})", path, raw),
+        }
+    }
+}
+
+/// SQLCipher codec parameters applied after `PRAGMA key` and before the key is verified.
+/// Unset fields keep whatever `cipher_compatibility` (or the bundled SQLCipher 4 defaults) selects.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CipherSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher_compatibility: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kdf_iter: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher_page_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher_hmac_algorithm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher_kdf_algorithm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher_use_hmac: Option<bool>,
+    /// Set automatically for files whose header was left unencrypted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher_plaintext_header_size: Option<u32>,
+    /// 16-byte salt as hex, required with a plaintext header unless the key carries it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher_salt: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,6 +100,23 @@ pub struct TableInfo {
     pub name: String,
     pub row_count: i64,
     pub columns: Vec<ColumnInfo>,
+    /// Primary key columns in key order; empty when the table declares no primary key
+    #[serde(default)]
+    pub primary_key: Vec<String>,
+    #[serde(default)]
+    pub foreign_keys: Vec<ForeignKeyInfo>,
+    /// Column lists of UNIQUE constraints, whether declared on a column or the table
+    #[serde(default)]
+    pub unique_constraints: Vec<Vec<String>>,
+    /// Table-level CHECK expressions; column-level ones are on the column
+    #[serde(default)]
+    pub checks: Vec<String>,
+    #[serde(default)]
+    pub autoincrement: bool,
+    #[serde(default)]
+    pub without_rowid: bool,
+    #[serde(default)]
+    pub strict: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,6 +126,158 @@ pub struct ColumnInfo {
     pub is_nullable: bool,
     pub default_value: Option<String>,
     pub is_primary_key: bool,
+    /// Declared `COLLATE`; `None` means BINARY
+    #[serde(default)]
+    pub collation: Option<String>,
+    #[serde(default)]
+    pub generated: Option<GeneratedColumn>,
+    /// Column-level CHECK expressions
+    #[serde(default)]
+    pub checks: Vec<String>,
+    /// Affinity SQLite gives `data_type`
+    #[serde(default)]
+    pub affinity: TypeAffinity,
+}
+
+/// The type affinity SQLite derives from a declared column type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypeAffinity {
+    Integer,
+    Text,
+    #[default]
+    Blob,
+    Real,
+    Numeric,
+}
+
+impl TypeAffinity {
+    /// The five rules of "Determination Of Column Affinity" in SQLite's datatype documentation,
+    /// applied in order to the declared type; an empty type has BLOB affinity
+    pub fn of(declared_type: &str) -> Self {
+        let declared = declared_type.to_ascii_uppercase();
+        let contains = |words: &[&str]| words.iter().any(|w| declared.contains(w));
+        if contains(&["INT"]) {
+            TypeAffinity::Integer
+        } else if contains(&["CHAR", "CLOB", "TEXT"]) {
+            TypeAffinity::Text
+        } else if contains(&["BLOB"]) || declared.trim().is_empty() {
+            TypeAffinity::Blob
+        } else if contains(&["REAL", "FLOA", "DOUB"]) {
+            TypeAffinity::Real
+        } else {
+            TypeAffinity::Numeric
+        }
+    }
+}
+
+impl std::fmt::Display for TypeAffinity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TypeAffinity::Integer => "INTEGER",
+            TypeAffinity::Text => "TEXT",
+            TypeAffinity::Blob => "BLOB",
+            TypeAffinity::Real => "REAL",
+            TypeAffinity::Numeric => "NUMERIC",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeneratedColumn {
+    pub expression: String,
+    /// STORED rather than VIRTUAL
+    pub stored: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForeignKeyInfo {
+    /// Child columns, in key order
+    pub columns: Vec<String>,
+    pub referenced_table: String,
+    /// Parent columns; empty when the key refers to the parent's primary key
+    pub referenced_columns: Vec<String>,
+    pub on_update: String,
+    pub on_delete: String,
+    pub deferrable: bool,
+    /// `DEFERRABLE INITIALLY DEFERRED`: checked at COMMIT rather than after each statement
+    pub initially_deferred: bool,
+}
+
+/// An index created with `CREATE INDEX`; indexes SQLite adds for UNIQUE and PRIMARY KEY
+/// constraints belong to the table definition and are not listed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexInfo {
+    pub name: String,
+    pub table_name: String,
+    pub is_unique: bool,
+    pub columns: Vec<IndexColumn>,
+    /// `WHERE` clause of a partial index
+    pub where_clause: Option<String>,
+    pub sql: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexColumn {
+    /// Table column, or `None` for an expression (or the rowid)
+    pub name: Option<String>,
+    /// The indexed term as written, set when it is an expression
+    pub expression: Option<String>,
+    pub descending: bool,
+    pub collation: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerInfo {
+    pub name: String,
+    pub table_name: String,
+    pub sql: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViewInfo {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub sql: String,
+}
+
+/// Every schema object of a database that `compare_schemas` looks at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseSchema {
+    pub tables: Vec<TableInfo>,
+    pub indexes: Vec<IndexInfo>,
+    pub triggers: Vec<TriggerInfo>,
+    pub views: Vec<ViewInfo>,
+    #[serde(default)]
+    pub pragmas: SchemaPragmas,
+}
+
+/// Database-wide settings that are part of the schema rather than of a connection
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaPragmas {
+    pub user_version: i64,
+    pub application_id: i64,
+    /// Text encoding, e.g. `UTF-8`
+    pub encoding: String,
+    /// 0 none, 1 full, 2 incremental
+    pub auto_vacuum: i64,
+}
+
+/// A schema saved to a file so a database can be compared against it later, e.g. the schema a
+/// release shipped with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaSnapshot {
+    /// `SchemaSnapshot::FORMAT_VERSION` of the writer; snapshots from newer versions are refused
+    pub format_version: u32,
+    /// Path of the database the snapshot was taken from
+    pub source: String,
+    /// Milliseconds since the Unix epoch
+    pub created_at: u64,
+    pub schema: DatabaseSchema,
+}
+
+impl SchemaSnapshot {
+    pub const FORMAT_VERSION: u32 = 1;
 }
 
 // Existing array-based TableData (for browse/schema)
@@ -50,6 +304,36 @@ pub struct SchemaComparison {
     pub removed_tables: Vec<String>,
     pub modified_tables: Vec<TableDiff>,
     pub identical_tables: Vec<String>,
+    pub added_indexes: Vec<String>,
+    pub removed_indexes: Vec<String>,
+    pub modified_indexes: Vec<ObjectDiff>,
+    pub added_triggers: Vec<String>,
+    pub removed_triggers: Vec<String>,
+    pub modified_triggers: Vec<ObjectDiff>,
+    pub added_views: Vec<String>,
+    pub removed_views: Vec<String>,
+    pub modified_views: Vec<ObjectDiff>,
+    /// Differences in `SchemaPragmas`, e.g. "user_version: 3 -> 4"
+    #[serde(default)]
+    pub pragma_changes: Vec<String>,
+}
+
+impl SchemaComparison {
+    pub fn has_differences(&self) -> bool {
+        !(self.added_tables.is_empty()
+            && self.removed_tables.is_empty()
+            && self.modified_tables.is_empty()
+            && self.added_indexes.is_empty()
+            && self.removed_indexes.is_empty()
+            && self.modified_indexes.is_empty()
+            && self.added_triggers.is_empty()
+            && self.removed_triggers.is_empty()
+            && self.modified_triggers.is_empty()
+            && self.added_views.is_empty()
+            && self.removed_views.is_empty()
+            && self.modified_views.is_empty()
+            && self.pragma_changes.is_empty())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,6 +342,18 @@ pub struct TableDiff {
     pub added_columns: Vec<ColumnInfo>,
     pub removed_columns: Vec<String>,
     pub modified_columns: Vec<ColumnDiff>,
+    pub added_foreign_keys: Vec<ForeignKeyInfo>,
+    pub removed_foreign_keys: Vec<ForeignKeyInfo>,
+    pub modified_foreign_keys: Vec<ForeignKeyDiff>,
+    /// Table-level changes: constraints and options such as WITHOUT ROWID
+    pub changes: Vec<String>,
+    /// Columns of both tables that changed places; the fewest moves that explain the new order
+    #[serde(default)]
+    pub moved_columns: Vec<ColumnMove>,
+    /// Removed and added columns that are probably one column renamed. They stay listed as
+    /// removed and added until the user confirms them (see `ColumnRename`).
+    #[serde(default)]
+    pub renamed_columns: Vec<RenamedColumn>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,4 +362,310 @@ pub struct ColumnDiff {
     pub old_type: String,
     pub new_type: String,
     pub changes: Vec<String>,
-}
\ No newline at end of file
+    /// Set when the declared type changed
+    #[serde(default)]
+    pub type_change: Option<TypeChange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypeChange {
+    /// Different spelling, same affinity, e.g. `VARCHAR(255)` and `TEXT`; SQLite stores and
+    /// compares values the same way
+    Cosmetic,
+    /// The column moves to another affinity, which changes how values are stored
+    Affinity,
+}
+
+/// Zero-based positions of a column in both tables
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnMove {
+    pub column_name: String,
+    pub old_position: usize,
+    pub new_position: usize,
+}
+
+/// A proposed rename: the columns have the same definition and either the same position or a
+/// similar name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenamedColumn {
+    pub old_name: String,
+    pub new_name: String,
+    pub old_position: usize,
+    pub new_position: usize,
+    /// Name similarity from 0 to 1
+    pub similarity: f64,
+}
+
+/// Foreign keys on the same child columns whose target or actions differ
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForeignKeyDiff {
+    pub columns: Vec<String>,
+    pub changes: Vec<String>,
+}
+
+/// An index, trigger or view present in both databases with a different definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectDiff {
+    pub name: String,
+    pub changes: Vec<String>,
+}
+
+/// SQL script that gives the target (database1 of the comparison) the schema of the source
+/// (database2). Added tables are created, removed ones dropped, modified ones altered in place
+/// or rebuilt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationScript {
+    pub source: String,
+    pub target: String,
+    pub sql: String,
+    pub tables: Vec<TableMigration>,
+    /// Indexes, triggers and views dropped, created or replaced
+    pub objects: Vec<ObjectMigration>,
+    /// Problems the script is expected to hit on existing data, e.g. NULLs in a new NOT NULL column
+    pub warnings: Vec<String>,
+}
+
+/// A rename the user confirmed, for the migration to carry out instead of a drop and an add
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnRename {
+    pub table_name: String,
+    pub old_name: String,
+    pub new_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableMigration {
+    pub table_name: String,
+    pub action: MigrationAction,
+    /// What an `Alter` changes, or why SQLite could not alter the table in place
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectMigration {
+    pub kind: SchemaObjectKind,
+    pub name: String,
+    pub action: MigrationAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaObjectKind {
+    Index,
+    Trigger,
+    View,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationAction {
+    Create,
+    Drop,
+    /// `ALTER TABLE ... ADD/DROP/RENAME COLUMN`
+    Alter,
+    /// Create the new table, copy the data across, drop the old one and rename
+    Rebuild,
+    /// Drop an index, trigger or view and create it again with the new definition
+    Replace,
+}
+
+/// Options for `compare_schemas`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchemaCompareOptions {
+    pub type_comparison: TypeComparison,
+}
+
+/// How strictly declared column types are compared
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypeComparison {
+    /// Report any change to the declared type, cosmetic ones included
+    #[default]
+    Declared,
+    /// Report only changes of affinity
+    Affinity,
+}
+
+/// Options for `compare_table_data`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DataCompareOptions {
+    /// Cap on the rows returned per list (added, removed, changed); the counts stay exact
+    pub max_rows: Option<usize>,
+    /// Compare digests of key ranges of this many rows first and only diff the ranges
+    /// whose digests differ. Suited to very large tables with few differences.
+    pub chunk_size: Option<u64>,
+    /// How rows are matched between the two tables; defaults to `RowKey::PrimaryKey`
+    pub key: Option<RowKey>,
+}
+
+/// Identity used to match rows of the same table in two databases
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RowKey {
+    /// The declared primary key (all of its columns), or `rowid` when the table has none
+    #[default]
+    PrimaryKey,
+    /// The rowid; only meaningful when both databases were copied from the same file
+    Rowid,
+    /// The columns of a unique index on the table in database1
+    UniqueIndex { name: String },
+    /// Columns chosen by the user; they must identify rows uniquely on both sides
+    Columns { columns: Vec<String> },
+}
+
+/// Row-level differences for one table, matched by key. `database1` is the old side and
+/// `database2` the new one, as in `SchemaComparison`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataComparison {
+    pub table_name: String,
+    pub database1: String,
+    pub database2: String,
+    pub key_columns: Vec<String>,
+    /// Columns present in both tables; only these are compared
+    pub compared_columns: Vec<String>,
+    /// Rows only in database2, as JSON objects { "col": value }
+    pub added_rows: Vec<serde_json::Value>,
+    /// Rows only in database1
+    pub removed_rows: Vec<serde_json::Value>,
+    pub changed_rows: Vec<RowDiff>,
+    pub summary: DataComparisonSummary,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataComparisonSummary {
+    pub database1_rows: u64,
+    pub database2_rows: u64,
+    pub identical: u64,
+    pub added: u64,
+    pub removed: u64,
+    pub changed: u64,
+    /// True when the row lists were capped; the counts above are always complete
+    pub truncated: bool,
+}
+
+impl DataComparisonSummary {
+    pub fn has_differences(&self) -> bool {
+        self.added + self.removed + self.changed > 0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowDiff {
+    /// Key columns of the row, as a JSON object
+    pub key: serde_json::Value,
+    pub changes: Vec<CellChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CellChange {
+    pub column: String,
+    pub old_value: serde_json::Value,
+    pub new_value: serde_json::Value,
+}
+
+/// Which database a data sync brings in line with the other
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncDirection {
+    /// Make database2's data match database1
+    #[default]
+    FirstToSecond,
+    /// Make database1's data match database2
+    SecondToFirst,
+}
+
+/// Options for generating or applying a data sync
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncOptions {
+    pub direction: SyncDirection,
+    pub include_inserts: bool,
+    pub include_updates: bool,
+    pub include_deletes: bool,
+    /// Row key per table; tables not listed are matched by `RowKey::PrimaryKey`
+    pub keys: HashMap<String, RowKey>,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            direction: SyncDirection::default(),
+            include_inserts: true,
+            include_updates: true,
+            include_deletes: true,
+            keys: HashMap::new(),
+        }
+    }
+}
+
+/// SQL script that transforms the target's data into the source's
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncScript {
+    /// Path of the database whose data is copied
+    pub source: String,
+    /// Path of the database the script is meant to run against
+    pub target: String,
+    pub sql: String,
+    pub tables: Vec<TableSyncSummary>,
+}
+
+/// Statement counts of a sync script, or affected row counts of an applied sync
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableSyncSummary {
+    pub table_name: String,
+    pub inserts: u64,
+    pub updates: u64,
+    pub deletes: u64,
+}
+
+/// Outcome of applying a data sync to the target database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncResult {
+    pub source: String,
+    pub target: String,
+    pub dry_run: bool,
+    /// False for a dry run, and when conflicts rolled the whole sync back
+    pub committed: bool,
+    pub tables: Vec<TableSyncSummary>,
+    pub conflicts: Vec<SyncConflict>,
+}
+
+/// A statement rejected by a constraint, or a foreign key left dangling by the sync
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConflict {
+    pub table_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statement: Option<String>,
+    pub message: String,
+}
+
+/// Payload of the `operation://progress` event emitted while a long-running command works
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationProgress {
+    pub operation_id: String,
+    pub phase: ProgressPhase,
+    /// Connection being read, when the phase works on a single database
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connection_id: Option<ConnectionId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_table: Option<String>,
+    pub done: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressPhase {
+    /// Counting rows and reading columns, one table at a time
+    LoadingTables,
+    /// Diffing the column lists of tables present in both databases
+    ComparingTables,
+    /// Fetching rows; `done`/`total` count rows
+    LoadingRows,
+    /// Matching rows of both tables by key; `done`/`total` count rows read from either side
+    ComparingRows,
+    /// Running sync statements against the target; `done`/`total` count statements
+    ApplyingChanges,
+}
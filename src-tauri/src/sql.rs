@@ -0,0 +1,351 @@
+//! Just enough SQL lexing to read what pragmas don't report from `sqlite_master.sql`
+
+use crate::database::ROWID;
+use crate::models::{ForeignKeyInfo, IndexColumn};
+
+/// The parts of a stored `CREATE TABLE` statement that migrations reuse
+pub(crate) struct TableDefinition {
+    /// Everything after the table name: the parenthesised definition and table options
+    pub(crate) body: String,
+    /// Top-level column definitions and table constraints, as written
+    pub(crate) items: Vec<String>,
+}
+
+impl TableDefinition {
+    /// `None` for virtual tables, whose definition belongs to their module
+    pub(crate) fn parse(sql: &str) -> Option<Self> {
+        let tokens = sql_tokens(sql);
+        let open = tokens.iter().position(|t| t.is_symbol("("))?;
+        if tokens[..open].iter().any(|t| t.kind == SqlTokenKind::Word && t.text == "virtual") {
+            return None;
+        }
+        let (items, _) = split_list(sql, &tokens, open)?;
+        Some(TableDefinition { body: sql[tokens[open].start..].to_string(), items })
+    }
+
+    /// Every `REFERENCES` clause, whether on a column or in a `FOREIGN KEY` table constraint
+    pub(crate) fn reference_clauses(&self) -> Vec<ReferenceClause> {
+        let mut clauses = Vec::new();
+        for item in &self.items {
+            let tokens = sql_tokens(item);
+            let Some(first) = tokens.first() else { continue };
+            let is_constraint = is_table_constraint(&tokens);
+            let starts: Vec<usize> = tokens.iter().enumerate()
+                .filter(|(_, t)| t.kind == SqlTokenKind::Word && t.text == "references")
+                .map(|(i, _)| i)
+                .collect();
+
+            for (n, &start) in starts.iter().enumerate() {
+                let columns = if is_constraint {
+                    // FOREIGN KEY (a, b) REFERENCES ...
+                    tokens[..start].iter().rposition(|t| t.is_symbol("("))
+                        .and_then(|open| split_list(item, &tokens, open))
+                        .map(|(columns, _)| columns.iter().filter_map(|c| sql_tokens(c).into_iter().next().map(|t| t.text)).collect())
+                        .unwrap_or_default()
+                } else {
+                    vec![first.text.clone()]
+                };
+                let clause = &tokens[start..starts.get(n + 1).copied().unwrap_or(tokens.len())];
+                let deferrable = clause.iter().enumerate().any(|(i, t)| {
+                    t.kind == SqlTokenKind::Word && t.text == "deferrable" && !(i > 0 && clause[i - 1].text == "not")
+                });
+                let initially_deferred = deferrable && clause.windows(2).any(|w| w[0].text == "initially" && w[1].text == "deferred");
+                clauses.push(ReferenceClause {
+                    columns,
+                    table: clause.get(1).map(|t| t.text.clone()).unwrap_or_default(),
+                    deferrable,
+                    initially_deferred,
+                });
+            }
+        }
+        clauses
+    }
+
+    /// The definition of a column, e.g. `"price" REAL NOT NULL DEFAULT 0`
+    pub(crate) fn column(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.items.iter().map(String::as_str).find(|item| {
+            let tokens = sql_tokens(item);
+            !is_table_constraint(&tokens) && tokens.first().is_some_and(|t| t.text == name)
+        })
+    }
+
+    /// CHECK expressions of the table constraints
+    pub(crate) fn constraint_checks(&self) -> Vec<String> {
+        self.items.iter()
+            .filter(|item| is_table_constraint(&sql_tokens(item)))
+            .flat_map(|item| DefinitionClauses::parse(item, 0).checks)
+            .collect()
+    }
+}
+
+/// Whether a top-level item of a table definition is a table constraint rather than a column
+pub(crate) fn is_table_constraint(tokens: &[SqlToken]) -> bool {
+    tokens.first().is_some_and(|t| {
+        t.kind == SqlTokenKind::Word && matches!(t.text.as_str(), "constraint" | "primary" | "unique" | "check" | "foreign")
+    })
+}
+
+/// Clauses of a column definition or table constraint that no pragma reports
+#[derive(Default)]
+pub(crate) struct DefinitionClauses {
+    pub(crate) collation: Option<String>,
+    /// Expression of `[GENERATED ALWAYS] AS (...)`
+    pub(crate) generated: Option<String>,
+    pub(crate) checks: Vec<String>,
+}
+
+impl DefinitionClauses {
+    /// Parse `item` from token `start` on; column definitions skip their name
+    pub(crate) fn parse(item: &str, start: usize) -> Self {
+        let tokens = sql_tokens(item);
+        let mut clauses = DefinitionClauses::default();
+        let mut i = start;
+        while i < tokens.len() {
+            let token = &tokens[i];
+            let opens_list = tokens.get(i + 1).is_some_and(|t| t.is_symbol("("));
+            if token.kind == SqlTokenKind::Word && token.text == "collate" {
+                clauses.collation = tokens.get(i + 1).map(|t| t.text.to_uppercase());
+                i += 2;
+                continue;
+            }
+            if token.kind == SqlTokenKind::Word && matches!(token.text.as_str(), "check" | "as") && opens_list {
+                if let Some(close) = matching_paren(&tokens, i + 1) {
+                    let expression = item[tokens[i + 1].end..tokens[close].start].trim().to_string();
+                    if token.text == "check" {
+                        clauses.checks.push(expression);
+                    } else {
+                        clauses.generated = Some(expression);
+                    }
+                    i = close + 1;
+                    continue;
+                }
+            }
+            // Skip anything else in parentheses: type sizes, DEFAULT expressions, column lists
+            if token.is_symbol("(") {
+                if let Some(close) = matching_paren(&tokens, i) {
+                    i = close + 1;
+                    continue;
+                }
+            }
+            i += 1;
+        }
+        clauses
+    }
+}
+
+pub(crate) fn matching_paren(tokens: &[SqlToken], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+pub(crate) fn same_checks(a: &[String], b: &[String]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_sql(a, b))
+}
+
+pub(crate) fn describe_checks(checks: &[String]) -> String {
+    if checks.is_empty() {
+        return "none".to_string();
+    }
+    checks.iter().map(|c| format!("CHECK ({})", c)).collect::<Vec<_>>().join(" ")
+}
+
+/// A `REFERENCES` clause as written; identifiers are lowercased like `SqlToken` text
+pub(crate) struct ReferenceClause {
+    pub(crate) columns: Vec<String>,
+    pub(crate) table: String,
+    pub(crate) deferrable: bool,
+    pub(crate) initially_deferred: bool,
+}
+
+impl ReferenceClause {
+    pub(crate) fn describes(&self, key: &ForeignKeyInfo) -> bool {
+        self.table == key.referenced_table.to_lowercase()
+            && self.columns.len() == key.columns.len()
+            && self.columns.iter().zip(&key.columns).all(|(a, b)| *a == b.to_lowercase())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SqlTokenKind {
+    /// Keyword, bare identifier or number
+    Word,
+    /// Identifier in `"..."`, `` `...` `` or `[...]`
+    Quoted,
+    /// String literal in `'...'`
+    Literal,
+    Symbol,
+}
+
+/// A token of stored schema SQL. Identifiers are compared case-insensitively, so words and
+/// quoted identifiers are lowercased; `start..end` is the byte range in the source.
+pub(crate) struct SqlToken {
+    pub(crate) kind: SqlTokenKind,
+    pub(crate) text: String,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+}
+
+impl SqlToken {
+    pub(crate) fn is_symbol(&self, symbol: &str) -> bool {
+        self.kind == SqlTokenKind::Symbol && self.text == symbol
+    }
+}
+
+/// Just enough of a lexer to find identifiers and parentheses in `sqlite_master.sql`
+pub(crate) fn sql_tokens(sql: &str) -> Vec<SqlToken> {
+    let bytes = sql.as_bytes();
+    let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b == b'$' || b >= 0x80;
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let (kind, text) = match bytes[i] {
+            b if b.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/')) {
+                    i += 1;
+                }
+                i = (i + 2).min(bytes.len());
+                continue;
+            }
+            quote @ (b'\'' | b'"' | b'`' | b'[') => {
+                let close = if quote == b'[' { b']' } else { quote };
+                let mut text = Vec::new();
+                i += 1;
+                while i < bytes.len() {
+                    if bytes[i] == close {
+                        // Doubled quotes escape themselves, except inside [...]
+                        if close != b']' && bytes.get(i + 1) == Some(&close) {
+                            text.push(close);
+                            i += 2;
+                            continue;
+                        }
+                        i += 1;
+                        break;
+                    }
+                    text.push(bytes[i]);
+                    i += 1;
+                }
+                let text = String::from_utf8_lossy(&text).into_owned();
+                if quote == b'\'' {
+                    (SqlTokenKind::Literal, text)
+                } else {
+                    (SqlTokenKind::Quoted, text.to_lowercase())
+                }
+            }
+            b if is_word(b) => {
+                while i < bytes.len() && is_word(bytes[i]) {
+                    i += 1;
+                }
+                (SqlTokenKind::Word, sql[start..i].to_lowercase())
+            }
+            b => {
+                i += 1;
+                (SqlTokenKind::Symbol, (b as char).to_string())
+            }
+        };
+        tokens.push(SqlToken { kind, text, start, end: i });
+    }
+    tokens
+}
+
+/// Top-level comma-separated items of the parenthesised list opening at `tokens[open]`,
+/// and the index of the token that closes it
+pub(crate) fn split_list(sql: &str, tokens: &[SqlToken], open: usize) -> Option<(Vec<String>, usize)> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut item_start = tokens[open].end;
+    for (i, token) in tokens.iter().enumerate().skip(open + 1) {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") && depth > 0 {
+            depth -= 1;
+        } else if (token.is_symbol(")") || token.is_symbol(",")) && depth == 0 {
+            items.push(sql[item_start..token.start].trim().to_string());
+            if token.is_symbol(")") {
+                return Some((items, i));
+            }
+            item_start = token.end;
+        }
+    }
+    None
+}
+
+/// Indexed terms of a `CREATE INDEX` statement as written, and its `WHERE` clause if partial
+pub(crate) fn index_terms(sql: &str) -> (Vec<String>, Option<String>) {
+    let tokens = sql_tokens(sql);
+    let Some((terms, close)) = tokens.iter().position(|t| t.is_symbol("(")).and_then(|open| split_list(sql, &tokens, open)) else {
+        return (Vec::new(), None);
+    };
+    let where_clause = tokens.get(close + 1)
+        .filter(|t| t.kind == SqlTokenKind::Word && t.text == "where")
+        .map(|t| sql[t.end..].trim().trim_end_matches(';').trim_end().to_string());
+    (terms, where_clause)
+}
+
+pub(crate) fn describe_index_terms(columns: &[IndexColumn]) -> String {
+    columns.iter().map(|c| {
+        let mut term = c.expression.clone().or_else(|| c.name.clone()).unwrap_or_else(|| ROWID.to_string());
+        if !c.collation.eq_ignore_ascii_case("binary") && c.expression.is_none() {
+            term.push_str(&format!(" COLLATE {}", c.collation));
+        }
+        if c.descending && c.expression.is_none() {
+            term.push_str(" DESC");
+        }
+        term
+    }).collect::<Vec<_>>().join(", ")
+}
+
+/// Whether two pieces of schema SQL say the same thing, ignoring layout, comments, keyword
+/// case and identifier quoting
+pub(crate) fn same_sql(a: &str, b: &str) -> bool {
+    let normalize = |sql: &str| {
+        sql_tokens(sql).into_iter().map(|t| {
+            let identifier = matches!(t.kind, SqlTokenKind::Word | SqlTokenKind::Quoted);
+            (identifier, t.kind == SqlTokenKind::Literal, t.text)
+        }).collect::<Vec<_>>()
+    };
+    normalize(a) == normalize(b)
+}
+
+pub(crate) fn same_optional_sql(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => same_sql(a, b),
+        (a, b) => a == b,
+    }
+}
+
+/// Whether `sql` refers to the identifier `name` anywhere outside string literals
+pub(crate) fn mentions(sql: &str, name: &str) -> bool {
+    let name = name.to_lowercase();
+    sql_tokens(sql).iter().any(|t| matches!(t.kind, SqlTokenKind::Word | SqlTokenKind::Quoted) && t.text == name)
+}
+
+/// Whether `sql` contains the keywords `words` in sequence, e.g. `["primary", "key"]`
+pub(crate) fn has_keywords(sql: &str, words: &[&str]) -> bool {
+    let tokens = sql_tokens(sql);
+    tokens.windows(words.len()).any(|window| {
+        window.iter().zip(words).all(|(t, word)| t.kind == SqlTokenKind::Word && t.text == *word)
+    })
+}
@@ -0,0 +1,128 @@
+//! Fixture databases generated at test time in a temporary directory
+
+#![allow(dead_code)]
+
+use rusqlite::Connection;
+use sqlcipher_tool::models::{ConnectionId, KeyMaterial, OpenMode};
+use sqlcipher_tool::DatabaseManager;
+use tempfile::TempDir;
+
+/// Raw 32-byte key used by the raw-key fixtures
+pub const RAW_KEY: &str = "2DD29CA851E7B56E4697B0E1F08507293D761A05CE4D1B628663F411A8086D99";
+
+/// A directory of fixture databases, removed when dropped
+pub struct Fixtures {
+    dir: TempDir,
+}
+
+impl Fixtures {
+    pub fn new() -> Self {
+        Self { dir: TempDir::new().expect("create fixture directory") }
+    }
+
+    pub fn path(&self, name: &str) -> String {
+        self.dir.path().join(name).to_string_lossy().into_owned()
+    }
+
+    /// Plaintext database built from `sql`
+    pub fn plaintext(&self, name: &str, sql: &str) -> String {
+        self.create(name, None, &[], sql)
+    }
+
+    /// SQLCipher database keyed with `key` (a passphrase, or `x'…'` for a raw key), with
+    /// `cipher_pragmas` applied before the first write
+    pub fn encrypted(&self, name: &str, key: &str, cipher_pragmas: &[(&str, &str)], sql: &str) -> String {
+        self.create(name, Some(key), cipher_pragmas, sql)
+    }
+
+    fn create(&self, name: &str, key: Option<&str>, cipher_pragmas: &[(&str, &str)], sql: &str) -> String {
+        let path = self.path(name);
+        let conn = Connection::open(&path).expect("create fixture");
+        if let Some(key) = key {
+            conn.pragma_update(None, "key", key).expect("set key");
+        }
+        for (pragma, value) in cipher_pragmas {
+            conn.pragma_update(None, pragma, value).expect("set cipher pragma");
+        }
+        conn.execute_batch(sql).expect("build fixture");
+        path
+    }
+
+    /// SQLCipher database with a 32-byte plaintext header, as iOS shared containers use. The
+    /// salt is not in the file, so it is returned alongside the path.
+    pub fn plaintext_header(&self, name: &str, key: &str, sql: &str) -> (String, String) {
+        let path = self.path(name);
+        let conn = Connection::open(&path).expect("create fixture");
+        conn.pragma_update(None, "key", key).expect("set key");
+        conn.pragma_update(None, "cipher_plaintext_header_size", 32).expect("set header size");
+        conn.execute_batch(sql).expect("build fixture");
+        let salt = conn.query_row("PRAGMA cipher_salt", [], |row| row.get(0)).expect("read salt");
+        (path, salt)
+    }
+
+    /// Write `contents` to a file in the fixture directory
+    pub fn file(&self, name: &str, contents: impl AsRef<[u8]>) -> String {
+        let path = self.path(name);
+        std::fs::write(&path, contents).expect("write fixture file");
+        path
+    }
+}
+
+/// Run `sql` against an existing fixture, e.g. a generated migration or sync script
+pub fn execute(path: &str, key: Option<&str>, sql: &str) {
+    let conn = Connection::open(path).expect("open fixture");
+    if let Some(key) = key {
+        conn.pragma_update(None, "key", key).expect("set key");
+    }
+    conn.execute_batch(sql).expect("run script");
+}
+
+/// The salt of an encrypted database, as hex: SQLCipher stores it in the first 16 bytes
+pub fn cipher_salt(path: &str) -> String {
+    let bytes = std::fs::read(path).expect("read fixture");
+    bytes[..16].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn passphrase(passphrase: &str) -> KeyMaterial {
+    KeyMaterial::Passphrase { passphrase: passphrase.to_string() }
+}
+
+/// Open `path` read-only with default cipher settings
+pub fn open(manager: &DatabaseManager, path: &str, key: &KeyMaterial) -> ConnectionId {
+    manager
+        .connect_database(path, None, key, None, false, OpenMode::ReadOnly)
+        .expect("connect to fixture")
+        .id
+}
+
+/// Open `path` for writing with default cipher settings
+pub fn open_writable(manager: &DatabaseManager, path: &str, key: &KeyMaterial) -> ConnectionId {
+    manager
+        .connect_database(path, None, key, None, false, OpenMode::ReadWrite)
+        .expect("connect to fixture")
+        .id
+}
+
+/// Schema shared by the inspection fixtures: a composite key, blobs, a view and a trigger
+pub const SAMPLE_SCHEMA: &str = "
+    CREATE TABLE customers (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        email TEXT UNIQUE
+    );
+    CREATE TABLE order_lines (
+        order_id INTEGER NOT NULL,
+        line_no INTEGER NOT NULL,
+        customer_id INTEGER REFERENCES customers(id),
+        amount REAL DEFAULT 0,
+        receipt BLOB,
+        PRIMARY KEY (line_no, order_id)
+    );
+    CREATE VIEW customer_totals AS
+        SELECT customer_id, SUM(amount) AS total FROM order_lines GROUP BY customer_id;
+    CREATE TRIGGER order_lines_positive BEFORE INSERT ON order_lines
+        WHEN NEW.amount < 0 BEGIN SELECT RAISE(ABORT, 'negative amount'); END;
+
+    INSERT INTO customers (id, name, email) VALUES (1, 'Ada', 'ada@example.com'), (2, 'Grace', NULL);
+    INSERT INTO order_lines VALUES (10, 1, 1, 9.5, x'00FF10'), (10, 2, 1, 1.25, NULL), (11, 1, 2, 3, x'');
+";
@@ -0,0 +1,180 @@
+//! Key handling of `connect_database` against plaintext and SQLCipher fixtures
+
+mod common;
+
+use common::*;
+use sqlcipher_tool::database::{CancelToken, DatabaseType};
+use sqlcipher_tool::models::{CipherSettings, KeyMaterial, OpenMode};
+use sqlcipher_tool::{AppError, DatabaseManager};
+
+const TABLE: &str = "CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT); INSERT INTO notes (body) VALUES ('hello');";
+
+/// Error code of a failed connection attempt
+fn connect_error(manager: &DatabaseManager, path: &str, key: &KeyMaterial, settings: Option<CipherSettings>) -> &'static str {
+    let error = manager
+        .connect_database(path, None, key, settings, false, OpenMode::ReadOnly)
+        .expect_err("connection should fail");
+    AppError::from(error).code()
+}
+
+#[test]
+fn plaintext_database_opens_without_a_key() {
+    let fixtures = Fixtures::new();
+    let path = fixtures.plaintext("plain.db", TABLE);
+    let manager = DatabaseManager::new();
+
+    assert_eq!(manager.detect_database_type(&path).unwrap(), DatabaseType::Plain);
+    let info = manager.connect_database(&path, None, &passphrase(""), None, false, OpenMode::ReadOnly).unwrap();
+    assert_eq!(info.table_count, 1);
+    assert!(info.is_connected);
+    assert_eq!(info.open_mode, OpenMode::ReadOnly);
+}
+
+#[test]
+fn sqlcipher4_database_opens_with_its_passphrase() {
+    let fixtures = Fixtures::new();
+    let path = fixtures.encrypted("v4.db", "correct horse", &[], TABLE);
+    let manager = DatabaseManager::new();
+
+    assert_eq!(manager.detect_database_type(&path).unwrap(), DatabaseType::Encrypted);
+    let info = manager.connect_database(&path, None, &passphrase("correct horse"), None, false, OpenMode::ReadOnly).unwrap();
+    assert_eq!(info.table_count, 1);
+    assert_eq!(info.cipher_profile, None);
+}
+
+#[test]
+fn wrong_or_missing_key_is_reported_and_not_registered() {
+    let fixtures = Fixtures::new();
+    let path = fixtures.encrypted("v4.db", "correct horse", &[], TABLE);
+    let manager = DatabaseManager::new();
+
+    assert_eq!(connect_error(&manager, &path, &passphrase("battery staple"), None), "WRONG_KEY");
+    assert_eq!(connect_error(&manager, &path, &passphrase(""), None), "WRONG_KEY");
+    assert_eq!(connect_error(&manager, &path, &KeyMaterial::RawKey { key_hex: RAW_KEY.into() }, None), "WRONG_KEY");
+    assert!(manager.list_connections().is_empty());
+}
+
+#[test]
+fn sqlcipher3_database_needs_v3_settings_or_detection() {
+    let fixtures = Fixtures::new();
+    let path = fixtures.encrypted("v3.db", "it's a secret", &[("cipher_compatibility", "3")], TABLE);
+    let key = passphrase("it's a secret");
+    let manager = DatabaseManager::new();
+
+    assert_eq!(connect_error(&manager, &path, &key, None), "WRONG_KEY");
+
+    let compatibility = CipherSettings { cipher_compatibility: Some(3), ..Default::default() };
+    let info = manager.connect_database(&path, None, &key, Some(compatibility), false, OpenMode::ReadOnly).unwrap();
+    assert_eq!(info.table_count, 1);
+
+    let explicit = CipherSettings {
+        kdf_iter: Some(64000),
+        cipher_page_size: Some(1024),
+        cipher_hmac_algorithm: Some("HMAC_SHA1".into()),
+        cipher_kdf_algorithm: Some("PBKDF2_HMAC_SHA1".into()),
+        ..Default::default()
+    };
+    let info = manager.connect_database(&path, None, &key, Some(explicit), false, OpenMode::ReadOnly).unwrap();
+    assert_eq!(info.table_count, 1);
+
+    let info = manager.connect_database(&path, None, &key, None, true, OpenMode::ReadOnly).unwrap();
+    assert_eq!(info.cipher_profile.as_deref(), Some("sqlcipher3"));
+    assert_eq!(manager.get_tables(&info.id, &CancelToken::new()).unwrap().len(), 1);
+}
+
+#[test]
+fn detection_still_rejects_a_wrong_key() {
+    let fixtures = Fixtures::new();
+    let path = fixtures.encrypted("v3.db", "secret", &[("cipher_compatibility", "3")], TABLE);
+    let manager = DatabaseManager::new();
+
+    let error = manager
+        .connect_database(&path, None, &passphrase("not it"), None, true, OpenMode::ReadOnly)
+        .unwrap_err();
+    assert_eq!(AppError::from(error).code(), "WRONG_KEY");
+}
+
+#[test]
+fn raw_keys_open_databases_keyed_with_hex() {
+    let fixtures = Fixtures::new();
+    let path = fixtures.encrypted("raw.db", &format!("x'{}'", RAW_KEY), &[], TABLE);
+    let manager = DatabaseManager::new();
+
+    open(&manager, &path, &KeyMaterial::RawKey { key_hex: RAW_KEY.into() });
+    open(&manager, &path, &KeyMaterial::RawKey { key_hex: format!("x'{}'", RAW_KEY.to_lowercase()) });
+
+    let salt = cipher_salt(&path);
+    open(&manager, &path, &KeyMaterial::RawKeyWithSalt { key_hex: RAW_KEY.into(), salt_hex: salt });
+
+    assert_eq!(connect_error(&manager, &path, &KeyMaterial::RawKey { key_hex: "abc".into() }, None), "INVALID_INPUT");
+    assert_eq!(connect_error(&manager, &path, &KeyMaterial::RawKey { key_hex: "zz".repeat(32) }, None), "INVALID_INPUT");
+}
+
+#[test]
+fn key_files_hold_passphrases_or_raw_keys() {
+    let fixtures = Fixtures::new();
+    let manager = DatabaseManager::new();
+
+    let path = fixtures.encrypted("passphrase.db", "from a file", &[], TABLE);
+    let key_file = fixtures.file("passphrase.txt", "from a file\n");
+    open(&manager, &path, &KeyMaterial::KeyFile { path: key_file, raw: false });
+
+    let path = fixtures.encrypted("raw.db", &format!("x'{}'", RAW_KEY), &[], TABLE);
+    let hex_file = fixtures.file("key.hex", format!("{}\n", RAW_KEY));
+    open(&manager, &path, &KeyMaterial::KeyFile { path: hex_file, raw: true });
+    let bytes: Vec<u8> = (0..RAW_KEY.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&RAW_KEY[i..i + 2], 16).unwrap())
+        .collect();
+    let binary_file = fixtures.file("key.bin", bytes);
+    open(&manager, &path, &KeyMaterial::KeyFile { path: binary_file, raw: true });
+
+    let missing = KeyMaterial::KeyFile { path: fixtures.path("missing.key"), raw: false };
+    assert!(manager.connect_database(&path, None, &missing, None, false, OpenMode::ReadOnly).is_err());
+}
+
+#[test]
+fn plaintext_header_database_opens_with_its_salt() {
+    let fixtures = Fixtures::new();
+    let (path, salt) = fixtures.plaintext_header("header.db", "pw", TABLE);
+    let manager = DatabaseManager::new();
+
+    assert_eq!(manager.detect_database_type(&path).unwrap(), DatabaseType::PlaintextHeaderEncrypted);
+    let settings = CipherSettings { cipher_salt: Some(salt), ..Default::default() };
+    let info = manager.connect_database(&path, None, &passphrase("pw"), Some(settings), false, OpenMode::ReadOnly).unwrap();
+    assert_eq!(info.table_count, 1);
+
+    // Without the salt SQLCipher derives the wrong key from the passphrase
+    assert_eq!(connect_error(&manager, &path, &passphrase("pw"), None), "WRONG_KEY");
+}
+
+#[test]
+fn detection_keeps_the_salt_of_a_plaintext_header_database() {
+    let fixtures = Fixtures::new();
+    let (path, salt) = fixtures.plaintext_header("header.db", "pw", TABLE);
+    let manager = DatabaseManager::new();
+
+    let settings = CipherSettings { cipher_salt: Some(salt.clone()), ..Default::default() };
+    let info = manager.connect_database(&path, None, &passphrase("pw"), Some(settings), true, OpenMode::ReadOnly).unwrap();
+    assert_eq!(info.cipher_profile.as_deref(), Some("sqlcipher4"));
+    assert_eq!(info.cipher_settings.and_then(|s| s.cipher_salt), Some(salt));
+}
+
+#[test]
+fn files_that_are_not_databases_are_rejected() {
+    let fixtures = Fixtures::new();
+    let manager = DatabaseManager::new();
+
+    let missing = fixtures.path("missing.db");
+    assert_eq!(manager.detect_database_type(&missing).unwrap(), DatabaseType::Missing);
+    assert_eq!(connect_error(&manager, &missing, &passphrase(""), None), "FILE_NOT_FOUND");
+    assert!(!std::path::Path::new(&missing).exists(), "connecting must not create the file");
+
+    let empty = fixtures.file("empty.db", b"");
+    assert_eq!(manager.detect_database_type(&empty).unwrap(), DatabaseType::Empty);
+    assert!(manager.connect_database(&empty, None, &passphrase(""), None, false, OpenMode::ReadOnly).is_err());
+
+    let text = fixtures.file("notes.txt", "plain text, not a database ".repeat(40));
+    assert_eq!(manager.detect_database_type(&text).unwrap(), DatabaseType::NotADatabase);
+    assert!(manager.connect_database(&text, None, &passphrase(""), None, false, OpenMode::ReadOnly).is_err());
+}
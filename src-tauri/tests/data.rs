@@ -0,0 +1,321 @@
+//! Keyed data comparison and data sync between generated fixtures
+
+mod common;
+
+use common::*;
+use serde_json::json;
+use sqlcipher_tool::database::CancelToken;
+use sqlcipher_tool::models::{DataCompareOptions, DataComparison, RowKey, SyncDirection, SyncOptions, TableSyncSummary};
+use sqlcipher_tool::{AppError, DatabaseManager};
+
+#[test]
+fn identifiers_with_quotes_are_compared_and_synced() {
+    let fixtures = Fixtures::new();
+    let schema = r#"CREATE TABLE "we""ird" ("i""d" INTEGER PRIMARY KEY, "va""l" TEXT);"#;
+    let source = fixtures.encrypted(
+        "source.db",
+        "k",
+        &[],
+        &format!(r#"{} INSERT INTO "we""ird" VALUES (1, 'one'), (2, 'two'), (3, 'three');"#, schema),
+    );
+    let target = fixtures.encrypted(
+        "target.db",
+        "k",
+        &[],
+        &format!(r#"{} INSERT INTO "we""ird" VALUES (1, 'one'), (2, 'TWO'), (4, 'four');"#, schema),
+    );
+    let manager = DatabaseManager::new();
+    let db1 = open(&manager, &source, &passphrase("k"));
+    let db2 = open_writable(&manager, &target, &passphrase("k"));
+    let cancel = CancelToken::new();
+    let table = r#"we"ird"#;
+
+    let comparison = manager.compare_table_data(&db1, &db2, table, &DataCompareOptions::default(), &cancel).unwrap();
+    assert_eq!(comparison.key_columns, [r#"i"d"#]);
+    assert_eq!((comparison.summary.added, comparison.summary.removed, comparison.summary.changed), (1, 1, 1));
+
+    let tables = [table.to_string()];
+    let result = manager.apply_sync(&db1, &db2, &tables, &SyncOptions::default(), false, &cancel).unwrap();
+    assert!(result.committed, "{:#?}", result.conflicts);
+    let comparison = manager.compare_table_data(&db1, &db2, table, &DataCompareOptions::default(), &cancel).unwrap();
+    assert!(!comparison.summary.has_differences(), "{:#?}", comparison);
+}
+
+#[test]
+fn table_names_are_matched_regardless_of_case() {
+    let fixtures = Fixtures::new();
+    let source = fixtures.plaintext("source.db", "CREATE TABLE Items (id INTEGER PRIMARY KEY, v); INSERT INTO Items VALUES (1, 'a'), (2, 'b');");
+    let target = fixtures.plaintext("target.db", "CREATE TABLE ITEMS (id INTEGER PRIMARY KEY, v); INSERT INTO ITEMS VALUES (1, 'a');");
+    let manager = DatabaseManager::new();
+    let db1 = open(&manager, &source, &passphrase(""));
+    let db2 = open_writable(&manager, &target, &passphrase(""));
+    let cancel = CancelToken::new();
+
+    let comparison = manager.compare_table_data(&db1, &db2, "items", &DataCompareOptions::default(), &cancel).unwrap();
+    assert_eq!(comparison.table_name, "Items", "reported as database1 spells it");
+    assert_eq!((comparison.summary.added, comparison.summary.removed), (0, 1));
+
+    let tables = ["items".to_string()];
+    let result = manager.apply_sync(&db1, &db2, &tables, &SyncOptions::default(), false, &cancel).unwrap();
+    assert!(result.committed, "{:#?}", result.conflicts);
+    let comparison = manager.compare_table_data(&db1, &db2, "ITEMS", &DataCompareOptions::default(), &cancel).unwrap();
+    assert!(!comparison.summary.has_differences(), "{:#?}", comparison);
+}
+
+#[test]
+fn chunked_digests_find_the_same_differences_as_a_full_merge() {
+    let fixtures = Fixtures::new();
+    let schema = "CREATE TABLE readings (sensor INTEGER, channel TEXT, value, PRIMARY KEY (sensor, channel));
+        WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < 6000)
+        INSERT INTO readings SELECT x / 3, 'c' || (x % 3), x FROM n;
+        INSERT INTO readings VALUES (NULL, 'spare', 1), (NULL, 'backup', 2);";
+    let old = fixtures.plaintext("old.db", schema);
+    let new = fixtures.plaintext(
+        "new.db",
+        &format!(
+            "{}
+            UPDATE readings SET value = value + 1 WHERE sensor IN (17, 1900);
+            UPDATE readings SET value = CAST(value AS REAL) WHERE sensor = 700;
+            DELETE FROM readings WHERE sensor = 1200;
+            INSERT INTO readings VALUES (-5, 'z', 0), (99999, 'z', 0), (50, 'c9', 1), (NULL, 'new', 3);
+            UPDATE readings SET value = 3 WHERE channel = 'backup';",
+            schema
+        ),
+    );
+    let manager = DatabaseManager::new();
+    let db1 = open(&manager, &old, &passphrase(""));
+    let db2 = open(&manager, &new, &passphrase(""));
+    let cancel = CancelToken::new();
+
+    let full = manager.compare_table_data(&db1, &db2, "readings", &DataCompareOptions::default(), &cancel).unwrap();
+    assert_eq!((full.summary.added, full.summary.removed, full.summary.changed), (4, 3, 7));
+    assert_eq!(full.summary.identical, 6002 - 3 - 7, "integral reals match integers");
+
+    for chunk_size in [1, 7, 1000, 100_000] {
+        let options = DataCompareOptions { chunk_size: Some(chunk_size), ..Default::default() };
+        let chunked = manager.compare_table_data(&db1, &db2, "readings", &options, &cancel).unwrap();
+        assert_eq!(chunked.summary, full.summary, "chunk size {}", chunk_size);
+        assert_eq!(chunked.added_rows, full.added_rows, "chunk size {}", chunk_size);
+        assert_eq!(chunked.removed_rows, full.removed_rows, "chunk size {}", chunk_size);
+        let changed = |c: &DataComparison| c.changed_rows.iter().map(|r| r.key.clone()).collect::<Vec<_>>();
+        assert_eq!(changed(&chunked), changed(&full), "chunk size {}", chunk_size);
+    }
+
+    let invalid = DataCompareOptions { chunk_size: Some(0), ..Default::default() };
+    let error = manager.compare_table_data(&db1, &db2, "readings", &invalid, &cancel).unwrap_err();
+    assert_eq!(AppError::from(error).code(), "INVALID_INPUT");
+}
+
+#[test]
+fn rows_are_matched_by_composite_primary_key() {
+    let fixtures = Fixtures::new();
+    let old = fixtures.encrypted("old.db", "k", &[], SAMPLE_SCHEMA);
+    let new = fixtures.encrypted(
+        "new.db",
+        "k",
+        &[],
+        &format!(
+            "{}
+            UPDATE order_lines SET amount = 2.5, customer_id = 2 WHERE order_id = 10 AND line_no = 2;
+            DELETE FROM order_lines WHERE order_id = 11;
+            INSERT INTO order_lines VALUES (12, 1, 2, 4, NULL);",
+            SAMPLE_SCHEMA
+        ),
+    );
+    let manager = DatabaseManager::new();
+    let (db1, db2) = (open(&manager, &old, &passphrase("k")), open(&manager, &new, &passphrase("k")));
+    let cancel = CancelToken::new();
+
+    let comparison = manager.compare_table_data(&db1, &db2, "order_lines", &DataCompareOptions::default(), &cancel).unwrap();
+    assert_eq!(comparison.key_columns, ["line_no", "order_id"], "key order, not column order");
+    assert_eq!(comparison.compared_columns, ["order_id", "line_no", "customer_id", "amount", "receipt"]);
+    let summary = &comparison.summary;
+    assert_eq!((summary.identical, summary.added, summary.removed, summary.changed), (1, 1, 1, 1));
+    assert_eq!((summary.database1_rows, summary.database2_rows), (3, 3));
+
+    assert_eq!(comparison.changed_rows.len(), 1);
+    let changed = &comparison.changed_rows[0];
+    assert_eq!(changed.key, json!({ "line_no": 2, "order_id": 10 }));
+    let changes: Vec<(&str, &serde_json::Value, &serde_json::Value)> = changed.changes.iter()
+        .map(|c| (c.column.as_str(), &c.old_value, &c.new_value))
+        .collect();
+    assert_eq!(changes, [("customer_id", &json!(1), &json!(2)), ("amount", &json!(1.25), &json!(2.5))]);
+    assert_eq!(comparison.removed_rows[0]["order_id"], json!(11));
+    assert_eq!(comparison.added_rows[0]["order_id"], json!(12));
+
+    let capped = DataCompareOptions { max_rows: Some(0), ..Default::default() };
+    let comparison = manager.compare_table_data(&db1, &db2, "order_lines", &capped, &cancel).unwrap();
+    assert!(comparison.summary.truncated);
+    assert!(comparison.added_rows.is_empty() && comparison.removed_rows.is_empty() && comparison.changed_rows.is_empty());
+    assert_eq!((comparison.summary.added, comparison.summary.removed, comparison.summary.changed), (1, 1, 1));
+}
+
+#[test]
+fn tables_without_a_primary_key_are_matched_by_rowid_or_a_chosen_key() {
+    let fixtures = Fixtures::new();
+    let schema = "CREATE TABLE notes (body TEXT, extra);
+        CREATE TABLE users (id INTEGER PRIMARY KEY, code TEXT, score);
+        CREATE UNIQUE INDEX users_code ON users (code);
+        CREATE INDEX users_score ON users (score);
+        CREATE TABLE pairs (a, b, PRIMARY KEY (b, a)) WITHOUT ROWID;
+        CREATE TABLE tags (name TEXT, note_id INTEGER);
+        INSERT INTO notes (body) VALUES ('one'), ('two'), ('three');
+        INSERT INTO tags VALUES ('red', 1);";
+    let old = fixtures.plaintext("old.db", &format!("{} INSERT INTO users VALUES (1, 'a', 1), (2, 'b', 2);", schema));
+    let new = fixtures.plaintext(
+        "new.db",
+        &format!(
+            "{}
+            ALTER TABLE notes DROP COLUMN extra;
+            UPDATE notes SET body = 'TWO' WHERE body = 'two';
+            INSERT INTO users VALUES (10, 'a', 1), (11, 'b', 3);
+            INSERT INTO tags VALUES ('red', 2);",
+            schema
+        ),
+    );
+    let manager = DatabaseManager::new();
+    let (db1, db2) = (open(&manager, &old, &passphrase("")), open(&manager, &new, &passphrase("")));
+    let cancel = CancelToken::new();
+    let compare = |table: &str, key: Option<RowKey>| {
+        manager.compare_table_data(&db1, &db2, table, &DataCompareOptions { key, ..Default::default() }, &cancel)
+    };
+
+    let notes = compare("notes", None).unwrap();
+    assert_eq!(notes.key_columns, ["rowid"]);
+    assert_eq!(notes.compared_columns, ["rowid", "body"], "columns on one side only are not compared");
+    assert_eq!((notes.summary.identical, notes.summary.changed), (2, 1));
+    let by_body = compare("notes", Some(RowKey::Columns { columns: vec!["body".into()] })).unwrap();
+    assert_eq!((by_body.summary.identical, by_body.summary.added, by_body.summary.removed), (2, 1, 1));
+
+    let users = compare("users", None).unwrap();
+    assert_eq!((users.summary.added, users.summary.removed), (2, 2));
+    let by_code = compare("users", Some(RowKey::UniqueIndex { name: "users_code".into() })).unwrap();
+    assert_eq!(by_code.key_columns, ["code"]);
+    assert_eq!((by_code.summary.identical, by_code.summary.changed), (0, 2), "ids and one score differ");
+
+    let invalid = |table: &str, key: RowKey| AppError::from(compare(table, Some(key)).unwrap_err()).code();
+    assert_eq!(invalid("users", RowKey::UniqueIndex { name: "users_score".into() }), "INVALID_INPUT");
+    assert_eq!(invalid("pairs", RowKey::Rowid), "INVALID_INPUT");
+    assert_eq!(invalid("tags", RowKey::Columns { columns: vec!["name".into()] }), "INVALID_INPUT");
+    assert_eq!(invalid("tags", RowKey::Columns { columns: vec!["missing".into()] }), "INVALID_INPUT");
+    assert_eq!(compare("pairs", None).unwrap().key_columns, ["b", "a"]);
+}
+
+#[test]
+fn sync_script_brings_the_target_in_line() {
+    let fixtures = Fixtures::new();
+    let schema = "CREATE TABLE readings (sensor INTEGER, channel TEXT, value, PRIMARY KEY (sensor, channel));
+        CREATE TABLE notes (id, body);
+        INSERT INTO notes VALUES (1, 'it''s'), (2, x'00FF'), (3, 1.5e300);";
+    let source = fixtures.encrypted(
+        "source.db",
+        "k",
+        &[],
+        &format!("{} INSERT INTO readings VALUES (1, 'a', 1), (2, 'b', 0.1), (3, 'c', x'DEAD'), (4, NULL, 'null channel');", schema),
+    );
+    let target = fixtures.encrypted(
+        "target.db",
+        "k",
+        &[],
+        &format!("{} INSERT INTO readings VALUES (1, 'a', 2), (3, 'c', NULL), (9, 'z', 'gone'); UPDATE notes SET body = 'x' WHERE id = 2;", schema),
+    );
+    let manager = DatabaseManager::new();
+    let (db1, db2) = (open(&manager, &source, &passphrase("k")), open(&manager, &target, &passphrase("k")));
+    let cancel = CancelToken::new();
+    let tables = ["readings".to_string(), "notes".to_string()];
+
+    let script = manager.generate_sync_script(&db1, &db2, &tables, &SyncOptions::default(), &cancel).unwrap();
+    assert_eq!(script.tables[0], TableSyncSummary { table_name: "readings".into(), inserts: 2, updates: 2, deletes: 1 });
+    assert_eq!(script.tables[1], TableSyncSummary { table_name: "notes".into(), inserts: 0, updates: 1, deletes: 0 });
+
+    execute(&target, Some("k"), &script.sql);
+    manager.reconnect_database(&db2, None).unwrap();
+    for table in &tables {
+        let comparison = manager.compare_table_data(&db1, &db2, table, &DataCompareOptions::default(), &cancel).unwrap();
+        assert!(!comparison.summary.has_differences(), "{:#?}", comparison);
+    }
+
+    let reverse = SyncOptions { direction: SyncDirection::SecondToFirst, ..Default::default() };
+    let script = manager.generate_sync_script(&db1, &db2, &tables, &reverse, &cancel).unwrap();
+    assert!(script.tables.iter().all(|t| t.inserts + t.updates + t.deletes == 0), "{}", script.sql);
+}
+
+#[test]
+fn applied_sync_leaves_no_differences_and_rolls_back_on_conflicts() {
+    let fixtures = Fixtures::new();
+    let schema = "CREATE TABLE parents (id INTEGER PRIMARY KEY, name TEXT UNIQUE);
+        CREATE TABLE children (id INTEGER PRIMARY KEY, parent_id REFERENCES parents(id));";
+    let source = fixtures.encrypted(
+        "source.db",
+        "k",
+        &[],
+        &format!("{} INSERT INTO parents VALUES (1, 'one'), (2, 'two'), (3, 'three'); INSERT INTO children VALUES (1, 1), (2, 3);", schema),
+    );
+    let target = fixtures.encrypted(
+        "target.db",
+        "k",
+        &[],
+        &format!("{} INSERT INTO parents VALUES (1, 'uno'), (4, 'four'); INSERT INTO children VALUES (1, 1), (5, 4);", schema),
+    );
+    let manager = DatabaseManager::new();
+    let db1 = open(&manager, &source, &passphrase("k"));
+    let db2 = open_writable(&manager, &target, &passphrase("k"));
+    let cancel = CancelToken::new();
+    let parents = ["parents".to_string()];
+    let both = ["parents".to_string(), "children".to_string()];
+    let options = SyncOptions::default();
+
+    let error = manager.apply_sync(&db2, &db1, &parents, &options, false, &cancel).unwrap_err();
+    assert_eq!(AppError::from(error).code(), "INVALID_INPUT", "read-only targets are refused");
+
+    // Deleting parent 4 alone would orphan child 5
+    let result = manager.apply_sync(&db1, &db2, &parents, &options, false, &cancel).unwrap();
+    assert!(!result.committed);
+    assert_eq!(result.conflicts.len(), 1, "{:#?}", result.conflicts);
+    assert_eq!(result.tables[0], TableSyncSummary { table_name: "parents".into(), inserts: 2, updates: 1, deletes: 1 });
+    let comparison = manager.compare_table_data(&db1, &db2, "parents", &DataCompareOptions::default(), &cancel).unwrap();
+    assert_eq!(comparison.summary.changed, 1, "the conflict rolled everything back");
+
+    let result = manager.apply_sync(&db1, &db2, &both, &options, true, &cancel).unwrap();
+    assert!(result.dry_run && !result.committed && result.conflicts.is_empty(), "{:#?}", result.conflicts);
+    let comparison = manager.compare_table_data(&db1, &db2, "children", &DataCompareOptions::default(), &cancel).unwrap();
+    assert_eq!(comparison.summary.added, 1, "a dry run changes nothing");
+
+    // Another target row already holds the name the sync inserts
+    manager.disconnect_database(&db2).unwrap();
+    execute(&target, Some("k"), "INSERT INTO parents VALUES (9, 'two');");
+    let db2 = open_writable(&manager, &target, &passphrase("k"));
+    let inserts_only = SyncOptions { include_deletes: false, ..Default::default() };
+    let result = manager.apply_sync(&db1, &db2, &parents, &inserts_only, false, &cancel).unwrap();
+    assert!(!result.committed);
+    assert!(result.conflicts.iter().any(|c| c.statement.is_some() && c.message.contains("UNIQUE")), "{:#?}", result.conflicts);
+
+    let result = manager.apply_sync(&db1, &db2, &both, &options, false, &cancel).unwrap();
+    assert!(result.committed && result.conflicts.is_empty(), "{:#?}", result.conflicts);
+    for table in &both {
+        let comparison = manager.compare_table_data(&db1, &db2, table, &DataCompareOptions::default(), &cancel).unwrap();
+        assert!(!comparison.summary.has_differences(), "{:#?}", comparison);
+    }
+}
+
+#[test]
+fn chunked_digests_report_a_repeated_key_like_a_full_merge() {
+    let fixtures = Fixtures::new();
+    let schema = "CREATE TABLE events (id INTEGER PRIMARY KEY, grp INTEGER, value);
+        WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < 2000)
+        INSERT INTO events SELECT x, x / 1000, x FROM n;";
+    let old = fixtures.plaintext("old.db", schema);
+    let new = fixtures.plaintext("new.db", &format!("{} UPDATE events SET value = -1 WHERE id = 1500;", schema));
+    let manager = DatabaseManager::new();
+    let (db1, db2) = (open(&manager, &old, &passphrase("")), open(&manager, &new, &passphrase("")));
+    let cancel = CancelToken::new();
+    let key = Some(RowKey::Columns { columns: vec!["grp".into()] });
+
+    let full = DataCompareOptions { key: key.clone(), ..Default::default() };
+    let error = manager.compare_table_data(&db1, &db2, "events", &full, &cancel).unwrap_err();
+    assert_eq!(AppError::from(error).code(), "INVALID_INPUT");
+
+    let chunked = DataCompareOptions { key, chunk_size: Some(1000), ..Default::default() };
+    let error = manager.compare_table_data(&db1, &db2, "events", &chunked, &cancel).unwrap_err();
+    assert_eq!(AppError::from(error).code(), "INVALID_INPUT");
+}
@@ -0,0 +1,331 @@
+//! `get_tables`, `get_table_data` and `compare_schemas` on generated fixtures
+
+mod common;
+
+use common::*;
+use serde_json::json;
+use sqlcipher_tool::database::CancelToken;
+use sqlcipher_tool::models::{
+    CipherSettings, ConnectionId, OpenMode, OperationProgress, ProgressPhase, SchemaCompareOptions, SchemaComparison, SchemaSnapshot,
+    TypeAffinity, TypeChange, TypeComparison,
+};
+use sqlcipher_tool::{AppError, DatabaseManager, ProgressSink};
+use std::sync::{Arc, Mutex};
+
+fn compare(manager: &DatabaseManager, path1: &str, path2: &str) -> SchemaComparison {
+    let (db1, db2) = (open(manager, path1, &passphrase("k")), open(manager, path2, &passphrase("k")));
+    manager.compare_schemas(&db1, &db2, &SchemaCompareOptions::default(), &CancelToken::new()).unwrap()
+}
+
+#[test]
+fn get_tables_reports_columns_and_composite_keys() {
+    let fixtures = Fixtures::new();
+    let path = fixtures.encrypted("sample.db", "k", &[], SAMPLE_SCHEMA);
+    let manager = DatabaseManager::new();
+    let id = open(&manager, &path, &passphrase("k"));
+
+    let tables = manager.get_tables(&id, &CancelToken::new()).unwrap();
+    let names: Vec<&str> = tables.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, ["customers", "order_lines"], "views and internal tables are not listed");
+
+    let customers = &tables[0];
+    assert_eq!(customers.row_count, 2);
+    assert_eq!(customers.primary_key, ["id"]);
+    assert_eq!(customers.unique_constraints, [vec!["email".to_string()]]);
+    assert!(!customers.columns[1].is_nullable);
+
+    let lines = &tables[1];
+    assert_eq!(lines.row_count, 3);
+    assert_eq!(lines.primary_key, ["line_no", "order_id"], "key order, not column order");
+    let key_flags: Vec<(&str, bool)> = lines.columns.iter().map(|c| (c.name.as_str(), c.is_primary_key)).collect();
+    assert_eq!(
+        key_flags,
+        [("order_id", true), ("line_no", true), ("customer_id", false), ("amount", false), ("receipt", false)]
+    );
+    assert_eq!(lines.columns[3].default_value.as_deref(), Some("0"));
+    assert_eq!(lines.foreign_keys.len(), 1);
+    assert_eq!(lines.foreign_keys[0].columns, ["customer_id"]);
+    assert_eq!(lines.foreign_keys[0].referenced_table, "customers");
+}
+
+#[test]
+fn get_tables_reads_sqlcipher3_databases() {
+    let fixtures = Fixtures::new();
+    let path = fixtures.encrypted("v3.db", "k", &[("cipher_compatibility", "3")], SAMPLE_SCHEMA);
+    let manager = DatabaseManager::new();
+    let settings = CipherSettings { cipher_compatibility: Some(3), ..Default::default() };
+    let id = manager
+        .connect_database(&path, None, &passphrase("k"), Some(settings), false, OpenMode::ReadOnly)
+        .unwrap()
+        .id;
+
+    let tables = manager.get_tables(&id, &CancelToken::new()).unwrap();
+    assert_eq!(tables.len(), 2);
+    assert_eq!(tables[1].primary_key, ["line_no", "order_id"]);
+}
+
+#[test]
+fn get_table_data_returns_rows_with_blobs_and_nulls() {
+    let fixtures = Fixtures::new();
+    let path = fixtures.encrypted("sample.db", "k", &[], SAMPLE_SCHEMA);
+    let manager = DatabaseManager::new();
+    let id = open(&manager, &path, &passphrase("k"));
+    let cancel = CancelToken::new();
+
+    let data = manager.get_table_data(&id, "order_lines", None, &cancel).unwrap();
+    assert_eq!(data.columns, ["order_id", "line_no", "customer_id", "amount", "receipt"]);
+    assert_eq!(data.total_count, 3);
+    assert_eq!(
+        data.rows,
+        [
+            vec![json!(10), json!(1), json!(1), json!(9.5), json!("<BLOB 3 bytes>")],
+            vec![json!(10), json!(2), json!(1), json!(1.25), json!(null)],
+            vec![json!(11), json!(1), json!(2), json!(3.0), json!("<BLOB 0 bytes>")],
+        ]
+    );
+
+    let limited = manager.get_table_data(&id, "order_lines", Some(1), &cancel).unwrap();
+    assert_eq!(limited.rows.len(), 1);
+    assert_eq!(limited.total_count, 3);
+
+    // Table names match regardless of ASCII case, like they do in SQL
+    let upper = manager.get_table_data(&id, "ORDER_LINES", None, &cancel).unwrap();
+    assert_eq!((upper.columns, upper.rows), (data.columns, data.rows));
+
+    let error = manager.get_table_data(&id, "no_such_table", None, &cancel).unwrap_err();
+    assert_eq!(AppError::from(error).code(), "TABLE_NOT_FOUND");
+}
+
+#[test]
+fn closed_connections_are_not_readable() {
+    let fixtures = Fixtures::new();
+    let path = fixtures.plaintext("plain.db", SAMPLE_SCHEMA);
+    let manager = DatabaseManager::new();
+    let id = open(&manager, &path, &passphrase(""));
+    manager.disconnect_database(&id).unwrap();
+
+    let error = manager.get_tables(&id, &CancelToken::new()).unwrap_err();
+    assert_eq!(AppError::from(error).code(), "NOT_CONNECTED");
+}
+
+#[test]
+fn same_schema_is_identical_across_encryption_formats() {
+    let fixtures = Fixtures::new();
+    let plain = fixtures.plaintext("plain.db", SAMPLE_SCHEMA);
+    let v4 = fixtures.encrypted("v4.db", "k", &[], SAMPLE_SCHEMA);
+    let manager = DatabaseManager::new();
+    let plain_id = open(&manager, &plain, &passphrase(""));
+    let v4_id = open(&manager, &v4, &passphrase("k"));
+
+    let comparison = manager
+        .compare_schemas(&plain_id, &v4_id, &SchemaCompareOptions::default(), &CancelToken::new())
+        .unwrap();
+    assert!(!comparison.has_differences(), "{:#?}", comparison);
+    assert_eq!(comparison.identical_tables, ["customers", "order_lines"]);
+}
+
+#[test]
+fn compare_schemas_reports_table_view_and_trigger_changes() {
+    let fixtures = Fixtures::new();
+    let old = fixtures.encrypted("old.db", "k", &[], SAMPLE_SCHEMA);
+    let new = fixtures.encrypted(
+        "new.db",
+        "k",
+        &[],
+        &format!(
+            "{}
+            ALTER TABLE customers ADD COLUMN phone TEXT;
+            CREATE TABLE refunds (order_id INTEGER, amount REAL);
+            DROP VIEW customer_totals;
+            CREATE VIEW customer_totals AS
+                SELECT customer_id, SUM(amount) AS total, COUNT(*) AS lines FROM order_lines GROUP BY customer_id;
+            CREATE VIEW big_orders AS SELECT * FROM order_lines WHERE amount > 100;
+            DROP TRIGGER order_lines_positive;",
+            SAMPLE_SCHEMA
+        ),
+    );
+    let manager = DatabaseManager::new();
+
+    let comparison = compare(&manager, &old, &new);
+    assert!(comparison.has_differences());
+    assert_eq!(comparison.added_tables, ["refunds"]);
+    assert!(comparison.removed_tables.is_empty());
+    assert_eq!(comparison.identical_tables, ["order_lines"]);
+    assert_eq!(comparison.modified_tables.len(), 1);
+    let customers = &comparison.modified_tables[0];
+    assert_eq!(customers.table_name, "customers");
+    let added: Vec<&str> = customers.added_columns.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(added, ["phone"]);
+
+    assert_eq!(comparison.added_views, ["big_orders"]);
+    assert_eq!(comparison.modified_views.len(), 1);
+    assert_eq!(comparison.modified_views[0].name, "customer_totals");
+    assert_eq!(comparison.removed_triggers, ["order_lines_positive"]);
+
+    let reverse = compare(&manager, &new, &old);
+    assert_eq!(reverse.removed_tables, ["refunds"]);
+    assert_eq!(reverse.removed_views, ["big_orders"]);
+    assert_eq!(reverse.added_triggers, ["order_lines_positive"]);
+}
+
+#[test]
+fn compare_schemas_reports_primary_key_changes() {
+    let fixtures = Fixtures::new();
+    let old = fixtures.encrypted("old.db", "k", &[], SAMPLE_SCHEMA);
+    let new = fixtures.encrypted(
+        "new.db",
+        "k",
+        &[],
+        &SAMPLE_SCHEMA.replace("PRIMARY KEY (line_no, order_id)", "PRIMARY KEY (order_id, line_no)"),
+    );
+    let manager = DatabaseManager::new();
+
+    let comparison = compare(&manager, &old, &new);
+    assert_eq!(comparison.modified_tables.len(), 1);
+    let lines = &comparison.modified_tables[0];
+    assert_eq!(lines.table_name, "order_lines");
+    assert_eq!(lines.changes, ["primary key: (line_no, order_id) -> (order_id, line_no)"]);
+    assert!(lines.modified_columns.is_empty(), "{:#?}", lines);
+}
+
+#[test]
+fn compare_schemas_reports_progress_per_table() {
+    let fixtures = Fixtures::new();
+    let old = fixtures.plaintext("old.db", SAMPLE_SCHEMA);
+    let new = fixtures.plaintext("new.db", &format!("{} CREATE TABLE refunds (order_id INTEGER);", SAMPLE_SCHEMA));
+    let manager = DatabaseManager::new();
+    let (db1, db2) = (open(&manager, &old, &passphrase("")), open(&manager, &new, &passphrase("")));
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink: ProgressSink = {
+        let events = events.clone();
+        Arc::new(move |progress: OperationProgress| events.lock().unwrap().push(progress))
+    };
+    let cancel = CancelToken::with_progress("schemas", sink);
+    manager.compare_schemas(&db1, &db2, &SchemaCompareOptions::default(), &cancel).unwrap();
+
+    let events = events.lock().unwrap();
+    let seen = |phase: ProgressPhase, connection: Option<&ConnectionId>| -> Vec<(Option<String>, u64, u64)> {
+        events.iter()
+            .filter(|p| p.phase == phase && p.connection_id.as_ref() == connection)
+            .map(|p| (p.current_table.clone(), p.done, p.total))
+            .collect()
+    };
+    let loading = seen(ProgressPhase::LoadingTables, Some(&db2));
+    assert_eq!(loading.iter().filter_map(|(table, ..)| table.as_deref()).collect::<Vec<_>>(), ["customers", "order_lines", "refunds"]);
+    assert_eq!(
+        seen(ProgressPhase::ComparingTables, None),
+        [(Some("customers".to_string()), 0, 2), (Some("order_lines".to_string()), 1, 2), (None, 2, 2)]
+    );
+    assert!(events.iter().all(|p| p.operation_id == "schemas"));
+}
+
+#[test]
+fn declared_types_map_to_sqlite_affinities() {
+    let cases = [
+        ("INT", TypeAffinity::Integer),
+        ("BIGINT UNSIGNED", TypeAffinity::Integer),
+        ("CHARINT", TypeAffinity::Integer),
+        ("FLOATING POINT", TypeAffinity::Integer),
+        ("VARCHAR(255)", TypeAffinity::Text),
+        ("nclob", TypeAffinity::Text),
+        ("", TypeAffinity::Blob),
+        ("BLOB", TypeAffinity::Blob),
+        ("DOUBLE PRECISION", TypeAffinity::Real),
+        ("DECIMAL(10,5)", TypeAffinity::Numeric),
+        ("BOOLEAN", TypeAffinity::Numeric),
+        ("DATETIME", TypeAffinity::Numeric),
+        ("STRING", TypeAffinity::Numeric),
+    ];
+    for (declared, affinity) in cases {
+        assert_eq!(TypeAffinity::of(declared), affinity, "{:?}", declared);
+    }
+}
+
+#[test]
+fn compare_schemas_tells_cosmetic_type_changes_from_affinity_changes() {
+    let fixtures = Fixtures::new();
+    let old = fixtures.plaintext("old.db", "CREATE TABLE t (a INT, b VARCHAR(255), c TEXT, d integer, e REAL); CREATE TABLE u (x int);");
+    let new = fixtures.plaintext("new.db", "CREATE TABLE t (a INTEGER, b TEXT, c BLOB, d  INTEGER , e NUMERIC); CREATE TABLE u (x INTEGER);");
+    let manager = DatabaseManager::new();
+    let (db1, db2) = (open(&manager, &old, &passphrase("")), open(&manager, &new, &passphrase("")));
+    let cancel = CancelToken::new();
+
+    let declared = manager.compare_schemas(&db1, &db2, &SchemaCompareOptions::default(), &cancel).unwrap();
+    let t = declared.modified_tables.iter().find(|t| t.table_name == "t").unwrap();
+    let column = |name: &str| t.modified_columns.iter().find(|c| c.column_name == name);
+    assert_eq!(column("a").unwrap().type_change, Some(TypeChange::Cosmetic));
+    assert_eq!(column("b").unwrap().changes, ["type: VARCHAR(255) -> TEXT (cosmetic, TEXT affinity)"]);
+    assert_eq!(column("c").unwrap().changes, ["type: TEXT -> BLOB (affinity TEXT -> BLOB)"]);
+    assert!(column("d").is_none(), "case and spacing are not type changes");
+    assert_eq!(column("e").unwrap().type_change, Some(TypeChange::Affinity));
+    assert!(declared.modified_tables.iter().any(|t| t.table_name == "u"));
+
+    let options = SchemaCompareOptions { type_comparison: TypeComparison::Affinity };
+    let affinity = manager.compare_schemas(&db1, &db2, &options, &cancel).unwrap();
+    assert_eq!(affinity.identical_tables, ["u"]);
+    let t = affinity.modified_tables.iter().find(|t| t.table_name == "t").unwrap();
+    let names: Vec<&str> = t.modified_columns.iter().map(|c| c.column_name.as_str()).collect();
+    assert_eq!(names, ["c", "e"]);
+
+    let tables = manager.get_tables(&db1, &cancel).unwrap();
+    assert_eq!(tables[0].columns[1].affinity, TypeAffinity::Text);
+}
+
+#[test]
+fn schema_snapshots_compare_like_the_database_they_came_from() {
+    let fixtures = Fixtures::new();
+    let old = fixtures.encrypted(
+        "old.db",
+        "k",
+        &[],
+        "PRAGMA user_version = 3;
+        CREATE TABLE t (a INTEGER PRIMARY KEY, b TEXT NOT NULL);
+        CREATE INDEX t_b ON t (b);
+        CREATE VIEW v AS SELECT b FROM t;
+        CREATE TRIGGER tr AFTER INSERT ON t BEGIN SELECT 1; END;",
+    );
+    let new = fixtures.encrypted(
+        "new.db",
+        "k",
+        &[],
+        "PRAGMA user_version = 4;
+        CREATE TABLE t (a INTEGER PRIMARY KEY, b TEXT NOT NULL, c INT);
+        CREATE INDEX t_b ON t (b);
+        CREATE VIEW v AS SELECT b FROM t;",
+    );
+    let manager = DatabaseManager::new();
+    let (db1, db2) = (open(&manager, &old, &passphrase("k")), open(&manager, &new, &passphrase("k")));
+    let cancel = CancelToken::new();
+    let options = SchemaCompareOptions::default();
+    let path = fixtures.path("old.schema.json");
+
+    let snapshot = manager.save_schema_snapshot(&db1, &path, &cancel).unwrap();
+    assert_eq!(snapshot.format_version, SchemaSnapshot::FORMAT_VERSION);
+    assert_eq!(snapshot.schema.pragmas.user_version, 3);
+    assert_eq!(snapshot.schema.tables.len(), 1);
+
+    let own = manager.compare_schema_with_snapshot(&db1, &path, &options, &cancel).unwrap();
+    assert!(!own.has_differences(), "{:#?}", own);
+    assert_eq!(own.database2, path);
+
+    let live = manager.compare_schemas(&db2, &db1, &options, &cancel).unwrap();
+    let saved = manager.compare_schema_with_snapshot(&db2, &path, &options, &cancel).unwrap();
+    assert_eq!(serde_json::to_value(&saved.modified_tables).unwrap(), serde_json::to_value(&live.modified_tables).unwrap());
+    assert_eq!(saved.added_triggers, ["tr"]);
+    assert_eq!(saved.pragma_changes, ["user_version: 4 -> 3"]);
+    assert_eq!(saved.pragma_changes, live.pragma_changes);
+
+    for (name, contents) in [
+        ("text.json", "not json"),
+        ("empty.json", "{}"),
+        ("future.json", r#"{"format_version": 99}"#),
+        ("no_tables.json", r#"{"format_version": 1, "schema": {}}"#),
+    ] {
+        let bad = fixtures.file(name, contents);
+        let error = manager.compare_schema_with_snapshot(&db1, &bad, &options, &cancel).unwrap_err();
+        assert_eq!(AppError::from(error).code(), "INVALID_INPUT", "{}", name);
+    }
+    let error = manager.compare_schema_with_snapshot(&db1, &fixtures.path("missing.json"), &options, &cancel).unwrap_err();
+    assert_eq!(AppError::from(error).code(), "FILE_NOT_FOUND");
+}
@@ -0,0 +1,146 @@
+//! Migration scripts generated from schema diffs, applied back to the fixtures
+
+mod common;
+
+use common::*;
+use serde_json::json;
+use sqlcipher_tool::database::CancelToken;
+use sqlcipher_tool::models::{ColumnRename, ConnectionId, MigrationAction, MigrationScript, SchemaCompareOptions};
+use sqlcipher_tool::{AppError, DatabaseManager};
+
+fn action(script: &MigrationScript, table_name: &str) -> Option<MigrationAction> {
+    script.tables.iter().find(|t| t.table_name == table_name).map(|t| t.action)
+}
+
+/// Fail unless `compare_schemas` finds the two databases identical
+fn assert_same_schema(manager: &DatabaseManager, db1: &ConnectionId, db2: &ConnectionId) {
+    let comparison = manager.compare_schemas(db1, db2, &SchemaCompareOptions::default(), &CancelToken::new()).unwrap();
+    assert!(!comparison.has_differences(), "{:#?}", comparison);
+}
+
+#[test]
+fn applied_migration_script_leaves_no_schema_differences() {
+    let old = r#"
+        CREATE TABLE accounts (id INTEGER PRIMARY KEY, name TEXT, legacy TEXT);
+        CREATE TABLE invoices (id INTEGER PRIMARY KEY, amount TEXT, note);
+        CREATE TABLE payments (id INTEGER PRIMARY KEY, invoice_id REFERENCES invoices(id));
+        CREATE VIEW invoice_notes AS SELECT id, note FROM invoices;
+        CREATE TABLE pairs (a, b);
+        CREATE TABLE tagged (a, b);
+        CREATE INDEX tagged_b ON tagged (b);
+        CREATE TABLE "odd ""name""" (x);
+        CREATE TABLE retired (x);
+        INSERT INTO accounts VALUES (1, 'ada', 'old');
+        INSERT INTO invoices VALUES (1, '5', 'first'), (2, '7', 'second');
+        INSERT INTO payments VALUES (1, 1);
+        INSERT INTO pairs VALUES (1, 2);
+        INSERT INTO tagged VALUES (1, 2);
+        INSERT INTO "odd ""name""" VALUES (1);
+    "#;
+    let new = r#"
+        CREATE TABLE accounts (id INTEGER PRIMARY KEY, name TEXT, active INTEGER NOT NULL DEFAULT 0, "display, name" TEXT CHECK ("display, name" <> ''));
+        CREATE TABLE invoices (id INTEGER PRIMARY KEY, amount INTEGER NOT NULL DEFAULT 1, note);
+        CREATE INDEX invoices_amount ON invoices (amount);
+        CREATE TRIGGER invoices_noted AFTER INSERT ON invoices BEGIN UPDATE invoices SET note = 'new' WHERE id = NEW.id; END;
+        CREATE TABLE payments (id INTEGER PRIMARY KEY, invoice_id REFERENCES invoices(id));
+        CREATE VIEW invoice_notes AS SELECT id, note FROM invoices;
+        CREATE TABLE pairs (b, a);
+        CREATE TABLE tagged (a);
+        CREATE TABLE "odd ""name""" (x, y UNIQUE);
+        CREATE TABLE ledger (id INTEGER PRIMARY KEY, entry TEXT);
+        CREATE INDEX ledger_entry ON ledger (entry);
+    "#;
+    let fixtures = Fixtures::new();
+    let (old_path, new_path) = (fixtures.encrypted("old.db", "k", &[], old), fixtures.encrypted("new.db", "k", &[], new));
+    let manager = DatabaseManager::new();
+    let (db1, db2) = (open(&manager, &old_path, &passphrase("k")), open(&manager, &new_path, &passphrase("k")));
+    let cancel = CancelToken::new();
+
+    let script = manager.generate_migration_script(&db1, &db2, &[], &cancel).unwrap();
+    assert_eq!(action(&script, "accounts"), Some(MigrationAction::Alter));
+    assert_eq!(action(&script, "invoices"), Some(MigrationAction::Rebuild));
+    assert_eq!(action(&script, "pairs"), Some(MigrationAction::Rebuild), "column order changed");
+    assert_eq!(action(&script, "tagged"), Some(MigrationAction::Alter));
+    assert_eq!(action(&script, r#"odd "name""#), Some(MigrationAction::Rebuild), "UNIQUE columns cannot be added");
+    assert_eq!(action(&script, "retired"), Some(MigrationAction::Drop));
+    assert_eq!(action(&script, "ledger"), Some(MigrationAction::Create));
+    assert_eq!(action(&script, "payments"), None);
+
+    execute(&old_path, Some("k"), &script.sql);
+    manager.reconnect_database(&db1, None).unwrap();
+    assert_same_schema(&manager, &db1, &db2);
+    assert!(manager.generate_migration_script(&db1, &db2, &[], &cancel).unwrap().tables.is_empty());
+
+    // Rebuilt tables keep their rows, converted to the new column types
+    let invoices = manager.get_table_data(&db1, "invoices", None, &cancel).unwrap();
+    assert_eq!(invoices.rows, [vec![json!(1), json!(5), json!("first")], vec![json!(2), json!(7), json!("second")]]);
+    let pairs = manager.get_table_data(&db1, "pairs", None, &cancel).unwrap();
+    assert_eq!(pairs.columns, ["b", "a"]);
+    assert_eq!(pairs.rows, [vec![json!(2), json!(1)]]);
+    assert_eq!(manager.get_table_data(&db1, "payments", None, &cancel).unwrap().total_count, 1);
+}
+
+#[test]
+fn detected_renames_migrate_in_place_once_confirmed() {
+    let old = "CREATE TABLE people (id INTEGER PRIMARY KEY, name TEXT NOT NULL DEFAULT '', addr TEXT, age INT);
+        CREATE INDEX people_name ON people (name);
+        CREATE VIEW names AS SELECT name FROM people;
+        CREATE TABLE shuffled (a, b, c);
+        CREATE TABLE labels (x INT, y TEXT);
+        CREATE TABLE links (parent_id INTEGER REFERENCES people(id), v);
+        INSERT INTO people VALUES (1, 'ann', 'here', 3), (2, 'bob', 'there', 4);
+        INSERT INTO shuffled VALUES (1, 2, 3);
+        INSERT INTO labels VALUES (1, 'l');
+        INSERT INTO links VALUES (1, 'v');";
+    let new = "CREATE TABLE people (id INTEGER PRIMARY KEY, full_name TEXT NOT NULL DEFAULT '', address TEXT, age INT);
+        CREATE INDEX people_name ON people (full_name);
+        CREATE VIEW names AS SELECT full_name FROM people;
+        CREATE TABLE shuffled (b, c, a);
+        CREATE TABLE labels (x INT, label TEXT, z INT);
+        CREATE TABLE links (parentid INTEGER REFERENCES people(id), v);";
+    let fixtures = Fixtures::new();
+    let (old_path, new_path) = (fixtures.plaintext("old.db", old), fixtures.plaintext("new.db", new));
+    let manager = DatabaseManager::new();
+    let (db1, db2) = (open(&manager, &old_path, &passphrase("")), open(&manager, &new_path, &passphrase("")));
+    let cancel = CancelToken::new();
+
+    let comparison = manager.compare_schemas(&db1, &db2, &SchemaCompareOptions::default(), &cancel).unwrap();
+    let table = |name: &str| comparison.modified_tables.iter().find(|t| t.table_name == name).unwrap();
+    let renames = |name: &str| -> Vec<(String, String)> {
+        table(name).renamed_columns.iter().map(|r| (r.old_name.clone(), r.new_name.clone())).collect()
+    };
+    let pair = |old: &str, new: &str| (old.to_string(), new.to_string());
+    assert_eq!(renames("people"), [pair("name", "full_name"), pair("addr", "address")]);
+    assert_eq!(renames("labels"), [pair("y", "label")]);
+    assert_eq!(renames("links"), [pair("parent_id", "parentid")]);
+    assert!(table("people").moved_columns.is_empty(), "renames are not moves");
+    let moved = &table("shuffled").moved_columns;
+    assert_eq!(moved.len(), 1);
+    assert_eq!((moved[0].column_name.as_str(), moved[0].old_position, moved[0].new_position), ("a", 0, 2));
+
+    let unrelated = ColumnRename { table_name: "people".into(), old_name: "age".into(), new_name: "address".into() };
+    let error = manager.generate_migration_script(&db1, &db2, &[unrelated], &cancel).unwrap_err();
+    assert_eq!(AppError::from(error).code(), "INVALID_INPUT");
+
+    let confirmed: Vec<ColumnRename> = comparison.modified_tables.iter()
+        .flat_map(|t| t.renamed_columns.iter().map(|r| ColumnRename {
+            table_name: t.table_name.clone(),
+            old_name: r.old_name.clone(),
+            new_name: r.new_name.clone(),
+        }))
+        .collect();
+    let script = manager.generate_migration_script(&db1, &db2, &confirmed, &cancel).unwrap();
+    assert_eq!(action(&script, "people"), Some(MigrationAction::Alter));
+    assert_eq!(action(&script, "labels"), Some(MigrationAction::Alter));
+    assert_eq!(action(&script, "links"), Some(MigrationAction::Alter));
+    assert_eq!(action(&script, "shuffled"), Some(MigrationAction::Rebuild));
+
+    execute(&old_path, None, &script.sql);
+    manager.reconnect_database(&db1, None).unwrap();
+    assert_same_schema(&manager, &db1, &db2);
+    let people = manager.get_table_data(&db1, "people", None, &cancel).unwrap();
+    assert_eq!(people.columns, ["id", "full_name", "address", "age"]);
+    assert_eq!(people.rows[0], [json!(1), json!("ann"), json!("here"), json!(3)], "renamed columns keep their data");
+    let shuffled = manager.get_table_data(&db1, "shuffled", None, &cancel).unwrap();
+    assert_eq!(shuffled.rows, [vec![json!(2), json!(3), json!(1)]]);
+}
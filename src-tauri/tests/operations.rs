@@ -0,0 +1,64 @@
+//! Cancellation of operations that share a connection
+
+mod common;
+
+use common::*;
+use sqlcipher_tool::{AppError, DatabaseManager};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// A view that takes a moment to compute, so an operation can be caught mid-statement
+const SLOW_VIEW: &str = "CREATE VIEW slow AS
+    WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c LIMIT 3000000) SELECT sum(x) AS total FROM c;";
+
+#[test]
+fn cancelling_an_operation_waiting_for_a_connection_spares_the_one_running_on_it() {
+    let fixtures = Fixtures::new();
+    let path = fixtures.plaintext("slow.db", SLOW_VIEW);
+    let manager = Arc::new(DatabaseManager::new());
+    let id = open(&manager, &path, &passphrase(""));
+
+    let running = manager.begin_operation("running", std::slice::from_ref(&id), None).unwrap();
+    let worker = {
+        let (manager, id) = (manager.clone(), id.clone());
+        thread::spawn(move || manager.get_table_data(&id, "slow", None, &running))
+    };
+    thread::sleep(Duration::from_millis(200));
+
+    let waiting = manager.begin_operation("waiting", std::slice::from_ref(&id), None).unwrap();
+    let waiter = {
+        let (manager, id) = (manager.clone(), id.clone());
+        thread::spawn(move || manager.get_table_data(&id, "slow", None, &waiting))
+    };
+    thread::sleep(Duration::from_millis(100));
+    assert!(manager.cancel_operation("waiting"));
+
+    let data = worker.join().unwrap().expect("the running operation was not cancelled");
+    assert_eq!(data.rows.len(), 1);
+    let error = waiter.join().unwrap().unwrap_err();
+    assert_eq!(AppError::from(error).code(), "CANCELLED");
+    manager.end_operation("running");
+    manager.end_operation("waiting");
+}
+
+#[test]
+fn cancelling_the_running_operation_interrupts_it() {
+    let fixtures = Fixtures::new();
+    let path = fixtures.plaintext("slow.db", SLOW_VIEW);
+    let manager = Arc::new(DatabaseManager::new());
+    let id = open(&manager, &path, &passphrase(""));
+
+    let running = manager.begin_operation("running", std::slice::from_ref(&id), None).unwrap();
+    let worker = {
+        let (manager, id) = (manager.clone(), id.clone());
+        thread::spawn(move || manager.get_table_data(&id, "slow", None, &running))
+    };
+    thread::sleep(Duration::from_millis(200));
+    assert!(manager.cancel_operation("running"));
+
+    let error = worker.join().unwrap().unwrap_err();
+    assert_eq!(AppError::from(error).code(), "CANCELLED");
+    manager.end_operation("running");
+    assert!(!manager.cancel_operation("running"));
+}
@@ -1,17 +1,60 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 
 export interface DatabaseInfo {
+  id: string;
   path: string;
   name: string;
   table_count: number;
   is_connected: boolean;
   alias?: string;  // Add this line
+  cipher_settings?: CipherSettings;
+  cipher_profile?: string;
+  open_mode: OpenMode;
+  opened_at: number;
+}
+
+export type OpenMode = 'read_only' | 'immutable' | 'read_write';
+
+export type KeyMaterial =
+  | { kind: 'passphrase'; passphrase: string }
+  | { kind: 'raw_key'; key_hex: string }
+  | { kind: 'raw_key_with_salt'; key_hex: string; salt_hex: string }
+  | { kind: 'key_file'; path: string; raw?: boolean };
+
+export interface CipherSettings {
+  cipher_compatibility?: number;
+  kdf_iter?: number;
+  cipher_page_size?: number;
+  cipher_hmac_algorithm?: string;
+  cipher_kdf_algorithm?: string;
+  cipher_use_hmac?: boolean;
+  cipher_plaintext_header_size?: number;
+  cipher_salt?: string;
 }
 
 export interface TableInfo {
   name: string;
   row_count: number;
   columns: ColumnInfo[];
+  primary_key: string[];
+  foreign_keys: ForeignKeyInfo[];
+  unique_constraints: string[][];
+  checks: string[];
+  autoincrement: boolean;
+  without_rowid: boolean;
+  strict: boolean;
+}
+
+export interface ForeignKeyInfo {
+  columns: string[];
+  referenced_table: string;
+  // Empty when the key refers to the parent's primary key
+  referenced_columns: string[];
+  on_update: string;
+  on_delete: string;
+  deferrable: boolean;
+  initially_deferred: boolean;
 }
 
 export interface ColumnInfo {
@@ -20,6 +63,17 @@ export interface ColumnInfo {
   is_nullable: boolean;
   default_value?: string;
   is_primary_key: boolean;
+  collation?: string;
+  generated?: GeneratedColumn;
+  checks: string[];
+  affinity: TypeAffinity;
+}
+
+export type TypeAffinity = 'integer' | 'text' | 'blob' | 'real' | 'numeric';
+
+export interface GeneratedColumn {
+  expression: string;
+  stored: boolean;
 }
 
 export interface TableData {
@@ -35,6 +89,76 @@ export interface SchemaComparison {
   removed_tables: string[];
   modified_tables: TableDiff[];
   identical_tables: string[];
+  added_indexes: string[];
+  removed_indexes: string[];
+  modified_indexes: ObjectDiff[];
+  added_triggers: string[];
+  removed_triggers: string[];
+  modified_triggers: ObjectDiff[];
+  added_views: string[];
+  removed_views: string[];
+  modified_views: ObjectDiff[];
+  // e.g. "user_version: 3 -> 4"
+  pragma_changes: string[];
+}
+
+export interface SchemaPragmas {
+  user_version: number;
+  application_id: number;
+  encoding: string;
+  // 0 none, 1 full, 2 incremental
+  auto_vacuum: number;
+}
+
+export interface IndexColumn {
+  name?: string;
+  expression?: string;
+  descending: boolean;
+  collation: string;
+}
+
+export interface IndexInfo {
+  name: string;
+  table_name: string;
+  is_unique: boolean;
+  columns: IndexColumn[];
+  where_clause?: string;
+  sql: string;
+}
+
+export interface TriggerInfo {
+  name: string;
+  table_name: string;
+  sql: string;
+}
+
+export interface ViewInfo {
+  name: string;
+  columns: string[];
+  sql: string;
+}
+
+export interface DatabaseSchema {
+  tables: TableInfo[];
+  indexes: IndexInfo[];
+  triggers: TriggerInfo[];
+  views: ViewInfo[];
+  pragmas: SchemaPragmas;
+}
+
+export interface SchemaSnapshot {
+  format_version: number;
+  // Path of the database the snapshot was taken from
+  source: string;
+  // Milliseconds since the Unix epoch
+  created_at: number;
+  schema: DatabaseSchema;
+}
+
+// An index, trigger or view present in both databases with a different definition
+export interface ObjectDiff {
+  name: string;
+  changes: string[];
 }
 
 export interface TableDiff {
@@ -42,6 +166,40 @@ export interface TableDiff {
   added_columns: ColumnInfo[];
   removed_columns: string[];
   modified_columns: ColumnDiff[];
+  added_foreign_keys: ForeignKeyInfo[];
+  removed_foreign_keys: ForeignKeyInfo[];
+  modified_foreign_keys: ForeignKeyDiff[];
+  // Table-level changes: primary key, unique, check and table options
+  changes: string[];
+  moved_columns: ColumnMove[];
+  // Proposals only: the columns stay in removed_columns/added_columns until confirmed
+  renamed_columns: RenamedColumn[];
+}
+
+export interface ColumnMove {
+  column_name: string;
+  old_position: number;
+  new_position: number;
+}
+
+export interface RenamedColumn {
+  old_name: string;
+  new_name: string;
+  old_position: number;
+  new_position: number;
+  similarity: number;
+}
+
+// A confirmed rename for generateMigrationScript
+export interface ColumnRename {
+  table_name: string;
+  old_name: string;
+  new_name: string;
+}
+
+export interface ForeignKeyDiff {
+  columns: string[];
+  changes: string[];
 }
 
 export interface ColumnDiff {
@@ -49,6 +207,169 @@ export interface ColumnDiff {
   old_type: string;
   new_type: string;
   changes: string[];
+  // Cosmetic: different spelling, same affinity (VARCHAR(255) vs TEXT)
+  type_change?: 'cosmetic' | 'affinity';
+}
+
+// Script that gives database1 the schema of database2
+export interface MigrationScript {
+  source: string;
+  target: string;
+  sql: string;
+  tables: TableMigration[];
+  objects: ObjectMigration[];
+  warnings: string[];
+}
+
+export type MigrationAction = 'create' | 'drop' | 'alter' | 'rebuild' | 'replace';
+
+export type SchemaObjectKind = 'index' | 'trigger' | 'view';
+
+export interface ObjectMigration {
+  kind: SchemaObjectKind;
+  name: string;
+  action: MigrationAction;
+}
+
+export interface TableMigration {
+  table_name: string;
+  action: MigrationAction;
+  reasons: string[];
+}
+
+export interface SchemaCompareOptions {
+  // 'affinity' reports only type changes that change the column's affinity
+  type_comparison?: 'declared' | 'affinity';
+}
+
+export interface DataCompareOptions {
+  max_rows?: number;
+  // Compare digests of key ranges of this many rows first; suited to very large tables
+  chunk_size?: number;
+  key?: RowKey;
+}
+
+export type RowKey =
+  | { kind: 'primary_key' }
+  | { kind: 'rowid' }
+  | { kind: 'unique_index'; name: string }
+  | { kind: 'columns'; columns: string[] };
+
+export interface DataComparison {
+  table_name: string;
+  database1: string;
+  database2: string;
+  key_columns: string[];
+  compared_columns: string[];
+  added_rows: Record<string, any>[];
+  removed_rows: Record<string, any>[];
+  changed_rows: RowDiff[];
+  summary: DataComparisonSummary;
+}
+
+export interface DataComparisonSummary {
+  database1_rows: number;
+  database2_rows: number;
+  identical: number;
+  added: number;
+  removed: number;
+  changed: number;
+  truncated: boolean;
+}
+
+export interface RowDiff {
+  key: Record<string, any>;
+  changes: CellChange[];
+}
+
+export interface CellChange {
+  column: string;
+  old_value: any;
+  new_value: any;
+}
+
+export type SyncDirection = 'first_to_second' | 'second_to_first';
+
+export interface SyncOptions {
+  direction?: SyncDirection;
+  include_inserts?: boolean;
+  include_updates?: boolean;
+  include_deletes?: boolean;
+  // Row key per table; tables not listed are matched by primary key
+  keys?: Record<string, RowKey>;
+}
+
+export interface SyncScript {
+  source: string;
+  target: string;
+  sql: string;
+  tables: TableSyncSummary[];
+}
+
+export interface TableSyncSummary {
+  table_name: string;
+  inserts: number;
+  updates: number;
+  deletes: number;
+}
+
+export interface SyncResult {
+  source: string;
+  target: string;
+  dry_run: boolean;
+  // False for a dry run, and when conflicts rolled the whole sync back
+  committed: boolean;
+  tables: TableSyncSummary[];
+  conflicts: SyncConflict[];
+}
+
+export interface SyncConflict {
+  table_name: string;
+  statement?: string;
+  message: string;
+}
+
+export type ProgressPhase = 'loading_tables' | 'comparing_tables' | 'loading_rows' | 'comparing_rows' | 'applying_changes';
+
+// Payload of the `operation://progress` event
+export interface OperationProgress {
+  operation_id: string;
+  phase: ProgressPhase;
+  connection_id?: string;
+  current_table?: string;
+  done: number;
+  total: number;
+}
+
+export type ErrorCode =
+  | 'WRONG_KEY'
+  | 'NOT_A_DATABASE'
+  | 'FILE_NOT_FOUND'
+  | 'BUSY'
+  | 'TIMEOUT'
+  | 'CANCELLED'
+  | 'TABLE_NOT_FOUND'
+  | 'NOT_CONNECTED'
+  | 'INVALID_INPUT'
+  | 'DATABASE_ERROR'
+  | 'INTERNAL';
+
+// Shape of every error rejected by a backend command
+export interface CommandError {
+  code: ErrorCode;
+  message: string;
+  details?: string;
+}
+
+export function isCommandError(err: unknown): err is CommandError {
+  return typeof err === 'object' && err !== null && 'code' in err && 'message' in err;
+}
+
+export function formatCommandError(err: unknown): string {
+  if (isCommandError(err)) {
+    return err.details ? `${err.message}: ${err.details}` : err.message;
+  }
+  return err instanceof Error ? err.message : String(err);
 }
 
 export class DatabaseService {
@@ -56,29 +377,91 @@ export class DatabaseService {
     return await invoke('test_connection');
   }
 
-  async connectDatabase(path: string, password: string): Promise<DatabaseInfo> {
+  async connectDatabase(path: string, password: string, alias?: string, cipherSettings?: CipherSettings, detectCipher?: boolean, key?: KeyMaterial, openMode?: OpenMode): Promise<DatabaseInfo> {
     console.log('Connecting to database:', path);
-    return await invoke('connect_database', { path, password });
+    return await invoke('connect_database', { path, alias, password, key, cipherSettings, detectCipher, openMode });
+  }
+
+  async disconnectDatabase(connectionId: string): Promise<DatabaseInfo> {
+    return await invoke('disconnect_database', { connectionId });
+  }
+
+  async disconnectAll(): Promise<DatabaseInfo[]> {
+    return await invoke('disconnect_all');
   }
 
-  async getDatabaseTables(dbPath: string): Promise<TableInfo[]> {
-    console.log('Getting tables for database:', dbPath);
-    const result = await invoke('get_database_tables', { dbPath });
+  async listConnections(): Promise<DatabaseInfo[]> {
+    return await invoke('list_connections');
+  }
+
+  async reconnectDatabase(connectionId: string, openMode?: OpenMode): Promise<DatabaseInfo> {
+    return await invoke('reconnect_database', { connectionId, openMode });
+  }
+
+  async getDatabaseTables(connectionId: string, operationId?: string): Promise<TableInfo[]> {
+    console.log('Getting tables for database:', connectionId);
+    const result = await invoke('get_database_tables', { connectionId, operationId });
     console.log('Tables received from backend:', result);
     return result as TableInfo[];
   }
 
-  async getTableData(dbPath: string, tableName: string, limit?: number): Promise<TableData> {
-    console.log('Getting table data:', tableName, 'from', dbPath);
+  async getTableData(connectionId: string, tableName: string, limit?: number, operationId?: string): Promise<TableData> {
+    console.log('Getting table data:', tableName, 'from', connectionId);
     return await invoke('get_table_data', { 
-      dbPath, 
+      connectionId, 
       tableName, 
-      limit: limit || 100 
+      limit: limit || 100,
+      operationId
     });
   }
 
-  async compareDatabaseSchemas(db1Path: string, db2Path: string): Promise<SchemaComparison> {
-    console.log('Comparing schemas:', db1Path, 'vs', db2Path);
-    return await invoke('compare_database_schemas', { db1Path, db2Path });
+  async compareDatabaseSchemas(db1: string, db2: string, options?: SchemaCompareOptions, operationId?: string): Promise<SchemaComparison> {
+    console.log('Comparing schemas:', db1, 'vs', db2);
+    return await invoke('compare_database_schemas', { db1, db2, options, operationId });
+  }
+
+  async exportSchemaSnapshot(connectionId: string, path: string, operationId?: string): Promise<SchemaSnapshot> {
+    return await invoke('export_schema_snapshot', { connectionId, path, operationId });
+  }
+
+  // The connection is database1 of the result and the snapshot database2
+  async compareSchemaWithSnapshot(connectionId: string, snapshotPath: string, options?: SchemaCompareOptions, operationId?: string): Promise<SchemaComparison> {
+    console.log('Comparing schema of', connectionId, 'with snapshot', snapshotPath);
+    return await invoke('compare_schema_with_snapshot', { connectionId, snapshotPath, options, operationId });
+  }
+
+  async generateMigrationScript(db1: string, db2: string, renames: ColumnRename[] = [], operationId?: string): Promise<MigrationScript> {
+    return await invoke('generate_migration_script', { db1, db2, renames, operationId });
+  }
+
+  async compareTableData(db1: string, db2: string, tableName: string, options?: DataCompareOptions, operationId?: string): Promise<DataComparison> {
+    console.log('Comparing data of', tableName, ':', db1, 'vs', db2);
+    return await invoke('compare_table_data', { db1, db2, tableName, options, operationId });
+  }
+
+  async generateSyncScript(db1: string, db2: string, tables: string[], options?: SyncOptions, operationId?: string): Promise<SyncScript> {
+    return await invoke('generate_sync_script', { db1, db2, tables, options, operationId });
+  }
+
+  async applySync(db1: string, db2: string, tables: string[], options?: SyncOptions, dryRun = false, operationId?: string): Promise<SyncResult> {
+    return await invoke('apply_sync', { db1, db2, tables, options, dryRun, operationId });
+  }
+
+  async cancelOperation(operationId: string): Promise<boolean> {
+    return await invoke('cancel_operation', { operationId });
+  }
+
+  // Ids only need to be unique among running operations
+  newOperationId(): string {
+    return `op-ui-${Date.now()}-${Math.random().toString(36).slice(2, 8)}`;
+  }
+
+  // Progress updates for one operation; call the returned function to stop listening
+  async onOperationProgress(operationId: string, callback: (progress: OperationProgress) => void): Promise<UnlistenFn> {
+    return await listen<OperationProgress>('operation://progress', (event) => {
+      if (event.payload.operation_id === operationId) {
+        callback(event.payload);
+      }
+    });
   }
 }
\ No newline at end of file